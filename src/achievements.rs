@@ -0,0 +1,384 @@
+use macroquad::prelude::*;
+
+use crate::hud::{Anchor, Hud};
+use crate::profile::Profile;
+use crate::stats::{MatchStats, Side};
+use crate::theme::Theme;
+
+// Where the achievements are defined. A copy is built in too, like the emitters.
+pub const ACHIEVEMENT_FILE: &str = "achievements.txt";
+const BUILTIN_ACHIEVEMENTS: &str = include_str!("../achievements.txt");
+
+// How long an unlock stays up on screen, in seconds.
+const TOAST_TIME: f64 = 4.0;
+
+// The moments achievements get checked at.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Trigger {
+    Win,
+    Return,
+    Shrink,
+    Parry,
+    Goal,
+}
+
+impl Trigger {
+    pub const ALL: [Trigger; 5] = [
+        Trigger::Win,
+        Trigger::Return,
+        Trigger::Shrink,
+        Trigger::Parry,
+        Trigger::Goal,
+    ];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Trigger::Win => "win",
+            Trigger::Return => "return",
+            Trigger::Shrink => "shrink",
+            Trigger::Parry => "parry",
+            Trigger::Goal => "goal",
+        }
+    }
+}
+
+// Something that happened during a game, and which side did it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameEvent {
+    pub trigger: Trigger,
+    pub side: Side,
+    pub speed: f32, // Of the ball, for returns.
+}
+
+impl GameEvent {
+    pub fn new(trigger: Trigger, side: Side) -> Self {
+        GameEvent {
+            trigger,
+            side,
+            speed: 0.0,
+        }
+    }
+
+    pub fn speed(self, speed: f32) -> Self {
+        GameEvent { speed, ..self }
+    }
+}
+
+// A bound on a stat, for an achievement to count.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    AtLeast(String, f32),
+    AtMost(String, f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Achievement {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub when: Trigger,
+    pub conditions: Vec<Condition>,
+}
+
+impl Achievement {
+    fn new(id: &str) -> Self {
+        Achievement {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: String::new(),
+            when: Trigger::Win,
+            conditions: Vec::new(),
+        }
+    }
+
+    // Whether this event (and the match so far) earns it.
+    pub fn earned_by(&self, event: &GameEvent, stats: &MatchStats) -> bool {
+        if event.trigger != self.when {
+            return false;
+        }
+        let value = |name: &str| {
+            if name == "speed" {
+                Some(event.speed)
+            } else {
+                stats.stat(event.side, name)
+            }
+        };
+        self.conditions.iter().all(|condition| match condition {
+            Condition::AtLeast(name, bound) => value(name).is_some_and(|value| value >= *bound),
+            Condition::AtMost(name, bound) => value(name).is_some_and(|value| value <= *bound),
+        })
+    }
+}
+
+// Reading achievement definitions, in the same sort of format as the emitters.
+pub fn parse_achievements(source: &str) -> Result<Vec<Achievement>, String> {
+    let mut achievements: Vec<Achievement> = Vec::new();
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(id) = text
+            .strip_prefix('[')
+            .and_then(|text| text.strip_suffix(']'))
+        {
+            achievements.push(Achievement::new(id.trim()));
+            continue;
+        }
+        let Some(achievement) = achievements.last_mut() else {
+            return Err(format!(
+                "line {}: setting outside of an [achievement]",
+                line
+            ));
+        };
+        let (keyword, rest) = text.split_once(' ').unwrap_or((text, ""));
+        let rest = rest.trim();
+        match keyword {
+            "title" => achievement.title = rest.to_string(),
+            "description" => achievement.description = rest.to_string(),
+            "when" => {
+                achievement.when = Trigger::ALL
+                    .into_iter()
+                    .find(|trigger| trigger.keyword() == rest)
+                    .ok_or(format!("line {}: unknown trigger '{}'", line, rest))?;
+            }
+            "min" | "max" => {
+                let words = rest.split_whitespace().collect::<Vec<_>>();
+                let [name, bound] = words[..] else {
+                    return Err(format!(
+                        "line {}: {} takes a stat and a number",
+                        line, keyword
+                    ));
+                };
+                if name != "speed" && MatchStats::default().stat(Side::Left, name).is_none() {
+                    return Err(format!("line {}: unknown stat '{}'", line, name));
+                }
+                let bound = bound
+                    .parse::<f32>()
+                    .map_err(|_| format!("line {}: '{}' isn't a number", line, bound))?;
+                achievement.conditions.push(if keyword == "min" {
+                    Condition::AtLeast(name.to_string(), bound)
+                } else {
+                    Condition::AtMost(name.to_string(), bound)
+                });
+            }
+            other => return Err(format!("line {}: unknown setting '{}'", line, other)),
+        }
+    }
+    Ok(achievements)
+}
+
+// Loading the definitions from disk, falling back on the built-in copy if anything goes wrong.
+pub fn load_achievements() -> Vec<Achievement> {
+    std::fs::read_to_string(ACHIEVEMENT_FILE)
+        .map_err(|error| format!("{}: {}", ACHIEVEMENT_FILE, error))
+        .and_then(|source| {
+            parse_achievements(&source).map_err(|error| format!("{}: {}", ACHIEVEMENT_FILE, error))
+        })
+        .unwrap_or_else(|error| {
+            eprintln!(
+                "Couldn't load achievements, using the built-in ones. ({})",
+                error
+            );
+            parse_achievements(BUILTIN_ACHIEVEMENTS).unwrap_or_default()
+        })
+}
+
+// Every achievement, and the unlocks waiting to be shown off.
+pub struct Achievements {
+    pub list: Vec<Achievement>,
+    toasts: Vec<(usize, f64)>, // (Which achievement, when it was unlocked.)
+}
+
+impl Achievements {
+    pub fn new(list: Vec<Achievement>) -> Self {
+        Achievements {
+            list,
+            toasts: Vec::new(),
+        }
+    }
+
+    // Checking an event against everything not yet unlocked, saving any new ones to the profile.
+    // Returns true if anything was unlocked.
+    pub fn check(
+        &mut self,
+        event: &GameEvent,
+        stats: &MatchStats,
+        profile: &mut Profile,
+        current_time: f64,
+    ) -> bool {
+        let mut unlocked = false;
+        for (index, achievement) in self.list.iter().enumerate() {
+            if achievement.earned_by(event, stats) && profile.unlock_achievement(&achievement.id) {
+                self.toasts.push((index, current_time));
+                unlocked = true;
+            }
+        }
+        unlocked
+    }
+
+    // How many of the achievements the profile's got.
+    pub fn progress(&self, profile: &Profile) -> (usize, usize) {
+        let earned = self
+            .list
+            .iter()
+            .filter(|achievement| profile.achievements.contains(&achievement.id))
+            .count();
+        (earned, self.list.len())
+    }
+
+    // Popping up anything recently unlocked, one under the other, and fading them out.
+    pub fn draw_toasts(&mut self, hud: &Hud, theme: &Theme, current_time: f64) {
+        self.toasts
+            .retain(|(_index, unlocked_at)| current_time - unlocked_at < TOAST_TIME);
+        for (i, (index, unlocked_at)) in self.toasts.iter().enumerate() {
+            let achievement = &self.list[*index];
+            let fade = ((TOAST_TIME - (current_time - unlocked_at)) as f32).clamp(0.0, 1.0);
+            let y = 150.0 + i as f32 * 52.0;
+            hud.text(
+                &format!("Achievement unlocked: {}", achievement.title),
+                Anchor::Top,
+                (0.0, y),
+                24.0,
+                Color {
+                    a: fade,
+                    ..theme.warning
+                },
+            );
+            hud.text(
+                &achievement.description,
+                Anchor::Top,
+                (0.0, y + 26.0),
+                18.0,
+                Color {
+                    a: fade,
+                    ..theme.muted
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn achievement(source: &str) -> Achievement {
+        let mut list = parse_achievements(source).unwrap();
+        assert_eq!(list.len(), 1);
+        list.remove(0)
+    }
+
+    #[test]
+    fn built_in_achievements_parse() {
+        let list = parse_achievements(BUILTIN_ACHIEVEMENTS).unwrap();
+        assert!(!list.is_empty());
+        // And no two share an id, since the profile keys unlocks by it.
+        for (i, a) in list.iter().enumerate() {
+            assert!(list[i + 1..].iter().all(|b| b.id != a.id), "{}", a.id);
+        }
+    }
+
+    #[test]
+    fn parses_a_full_definition() {
+        let parsed = achievement(
+            "# Comment.
+[sharpshooter]
+title Sharpshooter   # Trailing comment.
+description Hit 5 shots.
+when win
+min shots_hit 5
+max damage_taken 2.5
+",
+        );
+        assert_eq!(parsed.id, "sharpshooter");
+        assert_eq!(parsed.title, "Sharpshooter");
+        assert_eq!(parsed.description, "Hit 5 shots.");
+        assert_eq!(parsed.when, Trigger::Win);
+        assert_eq!(
+            parsed.conditions,
+            vec![
+                Condition::AtLeast("shots_hit".to_string(), 5.0),
+                Condition::AtMost("damage_taken".to_string(), 2.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_trigger() {
+        assert_eq!(
+            parse_achievements("[a]\nwhen lose"),
+            Err("line 2: unknown trigger 'lose'".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_stat() {
+        assert_eq!(
+            parse_achievements("[a]\n\nmin style_points 3"),
+            Err("line 3: unknown stat 'style_points'".to_string())
+        );
+    }
+
+    #[test]
+    fn bounds_take_a_stat_and_a_number() {
+        for (source, error) in [
+            ("[a]\nmin returns", "line 2: min takes a stat and a number"),
+            (
+                "[a]\nmax returns 3 4",
+                "line 2: max takes a stat and a number",
+            ),
+            ("[a]\nmin", "line 2: min takes a stat and a number"),
+            ("[a]\nmin returns lots", "line 2: 'lots' isn't a number"),
+        ] {
+            assert_eq!(parse_achievements(source), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn settings_need_an_achievement() {
+        assert_eq!(
+            parse_achievements("title Orphan\n[a]"),
+            Err("line 1: setting outside of an [achievement]".to_string())
+        );
+        assert_eq!(
+            parse_achievements("[a]\ncolour red"),
+            Err("line 2: unknown setting 'colour'".to_string())
+        );
+    }
+
+    #[test]
+    fn earned_by_checks_the_trigger() {
+        let first_win = achievement("[first_win]\nwhen win");
+        let stats = MatchStats::default();
+        assert!(first_win.earned_by(&GameEvent::new(Trigger::Win, Side::Left), &stats));
+        assert!(!first_win.earned_by(&GameEvent::new(Trigger::Goal, Side::Left), &stats));
+    }
+
+    #[test]
+    fn earned_by_at_least_and_at_most() {
+        let tidy = achievement("[tidy]\nwhen win\nmin returns 3\nmax shots_fired 1");
+        let win = GameEvent::new(Trigger::Win, Side::Right);
+        let mut stats = MatchStats::default();
+        stats.side_mut(Side::Right).returns = 2;
+        assert!(!tidy.earned_by(&win, &stats)); // Too few returns.
+        stats.side_mut(Side::Right).returns = 3;
+        assert!(tidy.earned_by(&win, &stats)); // Right on both bounds.
+        stats.side_mut(Side::Right).shots_fired = 2;
+        assert!(!tidy.earned_by(&win, &stats)); // Too many shots.
+                                                // It goes by the event's side, not the other one.
+        stats.side_mut(Side::Right).shots_fired = 0;
+        assert!(!tidy.earned_by(&GameEvent::new(Trigger::Win, Side::Left), &stats));
+    }
+
+    #[test]
+    fn earned_by_speed_comes_from_the_event() {
+        let speed_demon = achievement("[speed_demon]\nwhen return\nmin speed 8");
+        let stats = MatchStats::default();
+        let slow = GameEvent::new(Trigger::Return, Side::Left).speed(7.9);
+        let fast = GameEvent::new(Trigger::Return, Side::Left).speed(8.0);
+        assert!(!speed_demon.earned_by(&slow, &stats));
+        assert!(speed_demon.earned_by(&fast, &stats));
+    }
+}
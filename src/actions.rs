@@ -0,0 +1,145 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::arena::arena_width;
+use crate::hud::{Anchor, Hud};
+use crate::theme::Theme;
+use crate::{Bounds, Transform};
+
+// Timings for the fancier paddle moves.
+const DASH_SPEED: f32 = 12.0;
+const DASH_COOLDOWN: f64 = 1.0;
+const PARRY_WINDOW: f64 = 0.2;
+const PARRY_COOLDOWN: f64 = 0.8;
+
+// A component for a paddle's dash and parry, plus the strip of arena it's allowed to roam.
+#[derive(Clone, Copy)]
+pub struct PaddleActions {
+    pub zone: (f32, f32), // (min x, max x)
+    pub dash_ready_at: f64,
+    pub parry_ready_at: f64,
+    pub parry_until: f64,
+}
+
+impl PaddleActions {
+    pub fn new(zone: (f32, f32)) -> Self {
+        PaddleActions {
+            zone,
+            dash_ready_at: 0.0,
+            parry_ready_at: 0.0,
+            parry_until: 0.0,
+        }
+    }
+
+    // Which way the paddle is facing (towards the middle of the arena.)
+    pub fn facing(&self) -> f32 {
+        if (self.zone.0 + self.zone.1) / 2.0 < arena_width() / 2.0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    // A burst of speed in whatever direction is held. Returns true if it went off.
+    pub fn try_dash(
+        &mut self,
+        transform: &mut Transform,
+        direction: (f32, f32),
+        current_time: f64,
+    ) -> bool {
+        let magnitude = (direction.0.powf(2.0) + direction.1.powf(2.0)).sqrt();
+        if current_time < self.dash_ready_at || magnitude == 0.0 {
+            return false;
+        }
+        self.dash_ready_at = current_time + DASH_COOLDOWN;
+        transform.velocity = (
+            transform.velocity.0 + direction.0 / magnitude * DASH_SPEED,
+            transform.velocity.1 + direction.1 / magnitude * DASH_SPEED,
+        );
+        true
+    }
+
+    pub fn try_parry(&mut self, current_time: f64) -> bool {
+        if current_time < self.parry_ready_at {
+            return false;
+        }
+        self.parry_ready_at = current_time + PARRY_COOLDOWN;
+        self.parry_until = current_time + PARRY_WINDOW;
+        true
+    }
+
+    pub fn is_parrying(&self, current_time: f64) -> bool {
+        current_time < self.parry_until
+    }
+
+    // How far along the cooldowns are, from 0 (just used) to 1 (ready.)
+    pub fn dash_charge(&self, current_time: f64) -> f32 {
+        (1.0 - (self.dash_ready_at - current_time) / DASH_COOLDOWN).clamp(0.0, 1.0) as f32
+    }
+
+    pub fn parry_charge(&self, current_time: f64) -> f32 {
+        (1.0 - (self.parry_ready_at - current_time) / PARRY_COOLDOWN).clamp(0.0, 1.0) as f32
+    }
+}
+
+// The strip each side gets to move around in.
+pub fn left_zone() -> (f32, f32) {
+    (32.0, arena_width() * 0.3)
+}
+
+pub fn right_zone() -> (f32, f32) {
+    (arena_width() * 0.7, arena_width() - 32.0)
+}
+
+// Keeping paddles inside their own zone.
+pub fn confine_paddles(world: &mut World) {
+    for (_id, (transform, actions)) in world.query_mut::<(&mut Transform, &PaddleActions)>() {
+        if transform.position.0 < actions.zone.0 || transform.position.0 > actions.zone.1 {
+            transform.position.0 = transform.position.0.clamp(actions.zone.0, actions.zone.1);
+            transform.velocity.0 = 0.0;
+        }
+    }
+}
+
+// The parry glow, and little cooldown bars under each paddle.
+pub fn draw_actions(world: &mut World, current_time: f64, theme: &Theme) {
+    for (_id, (transform, actions, bounds)) in
+        world.query_mut::<(&Transform, &PaddleActions, &Bounds)>()
+    {
+        if actions.is_parrying(current_time) {
+            draw_rectangle_lines(
+                transform.position.0 - bounds.0 - 6.0,
+                transform.position.1 - bounds.1 - 6.0,
+                bounds.0 * 2.0 + 12.0,
+                bounds.1 * 2.0 + 12.0,
+                4.0,
+                theme.parry,
+            );
+        }
+    }
+}
+
+// The dash and parry cooldowns, down in each side's bottom corner.
+pub fn draw_cooldowns(world: &mut World, current_time: f64, hud: &Hud, theme: &Theme) {
+    for (_id, (transform, actions)) in world.query_mut::<(&Transform, &PaddleActions)>() {
+        let (anchor, x) = if transform.position.0 < screen_width() / 2.0 {
+            (Anchor::BottomLeft, 32.0)
+        } else {
+            (Anchor::BottomRight, -32.0)
+        };
+        hud.bar(
+            anchor,
+            (x, -40.0),
+            (128.0, 4.0),
+            actions.dash_charge(current_time),
+            theme.foreground,
+        );
+        hud.bar(
+            anchor,
+            (x, -28.0),
+            (128.0, 4.0),
+            actions.parry_charge(current_time),
+            theme.parry,
+        );
+    }
+}
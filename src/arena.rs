@@ -0,0 +1,19 @@
+use std::sync::OnceLock;
+
+use macroquad::prelude::*;
+
+// A fixed size for the arena, for running without a window. Otherwise the arena is the screen.
+static FIXED_SIZE: OnceLock<(f32, f32)> = OnceLock::new();
+
+// Pinning the arena to a size. Only the first call counts.
+pub fn fix_arena_size(width: f32, height: f32) {
+    let _ = FIXED_SIZE.set((width, height));
+}
+
+pub fn arena_width() -> f32 {
+    FIXED_SIZE.get().map_or_else(screen_width, |size| size.0)
+}
+
+pub fn arena_height() -> f32 {
+    FIXED_SIZE.get().map_or_else(screen_height, |size| size.1)
+}
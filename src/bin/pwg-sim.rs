@@ -0,0 +1,403 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use hecs::*;
+use macroquad::prelude::*;
+use pong_with_guns::*;
+
+// And our constants.
+const ARENA_SIZE: (f32, f32) = (1280.0, 720.0);
+const STEP: f32 = 1.0 / 60.0; // Simulated seconds per frame.
+
+const HELP: &str = "Pong with Guns simulator: AI against AI, as fast as it'll go.
+
+Usage: pwg-sim [options]
+
+Options:
+  --matches <number>      How many matches to play. (Default: 100.)
+  --points <number>       Rounds needed to win a match. (Default: 5.)
+  --left <brain>          The left AI's brain. (Default: classic.)
+  --right <brain>         The right AI's brain. (Default: classic.)
+                          A brain is classic, gunner, tracker or ace, or a tracking strength and
+                          seconds between shots, like 1.2:0.8 (or 1.2:none.)
+  --mode <mode>           classic or multiball. (Default: classic.)
+  --level <file>          The arena to play in. (Default: the classic one.)
+  --seed <number>         Seed for the whole batch. (Default: 0.)
+  --time-limit <seconds>  Simulated seconds before a round is called off. (Default: 300.)
+  --format <csv|json>     csv prints a row per match (and a summary to stderr), json prints
+                          the summary, the score distribution and every match. (Default: csv.)
+  --help                  Show this and quit.
+";
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct SimOptions {
+    matches: u32,
+    points: i32,
+    left: (String, Brain),
+    right: (String, Brain),
+    mode: GameMode,
+    level: Option<PathBuf>,
+    seed: u64,
+    time_limit: f64,
+    format: Format,
+}
+
+impl Default for SimOptions {
+    fn default() -> Self {
+        SimOptions {
+            matches: 100,
+            points: POINTS_TO_WIN,
+            left: ("classic".to_string(), Brain::default()),
+            right: ("classic".to_string(), Brain::default()),
+            mode: GameMode::Classic,
+            level: None,
+            seed: 0,
+            time_limit: 300.0,
+            format: Format::Csv,
+        }
+    }
+}
+
+impl SimOptions {
+    fn parse(args: &[String]) -> Result<SimOptions, String> {
+        let mut options = SimOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                return Err(format!("unexpected argument '{}'", arg));
+            };
+            if flag == "help" {
+                print!("{}", HELP);
+                std::process::exit(0);
+            }
+            let value = args
+                .next()
+                .ok_or(format!("--{} needs a value", flag))?
+                .as_str();
+            let number = || format!("'{}' isn't a number", value);
+            match flag {
+                "matches" => options.matches = value.parse().map_err(|_| number())?,
+                "points" => {
+                    options.points = value
+                        .parse()
+                        .ok()
+                        .filter(|points| *points > 0)
+                        .ok_or_else(number)?
+                }
+                "left" => options.left = (value.to_string(), Brain::parse(value)?),
+                "right" => options.right = (value.to_string(), Brain::parse(value)?),
+                "mode" => {
+                    options.mode = [GameMode::Classic, GameMode::Multiball]
+                        .into_iter()
+                        .find(|mode| mode.label().eq_ignore_ascii_case(value))
+                        .ok_or(format!("the simulator can't play '{}'", value))?
+                }
+                "level" => options.level = Some(PathBuf::from(value)),
+                "seed" => options.seed = value.parse().map_err(|_| number())?,
+                "time-limit" => options.time_limit = value.parse().map_err(|_| number())?,
+                "format" => {
+                    options.format = match value {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format '{}'", other)),
+                    }
+                }
+                other => return Err(format!("unknown option '--{}'", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// How a single match went.
+#[derive(Default, Clone, Debug)]
+struct MatchResult {
+    winner: Option<Side>, // None if a round got called off.
+    left_score: i32,      // Rounds won. (In multiball a round takes a few goals.)
+    right_score: i32,
+    rallies: Vec<u32>, // The longest rally of each round.
+    duration: f64,     // Simulated seconds.
+}
+
+impl MatchResult {
+    fn longest_rally(&self) -> u32 {
+        self.rallies.iter().copied().max().unwrap_or(0)
+    }
+
+    fn average_rally(&self) -> f32 {
+        if self.rallies.is_empty() {
+            0.0
+        } else {
+            self.rallies.iter().sum::<u32>() as f32 / self.rallies.len() as f32
+        }
+    }
+}
+
+// Playing one match out, first to `points` rounds, with the same physics as the game.
+fn play_match(options: &SimOptions, level: &Level) -> MatchResult {
+    let mut world = World::new();
+    let mut game_state = GameState::new();
+    game_state.mode = options.mode;
+    game_state.points_to_win = options.points;
+    let mut particles = ParticleStorage::new(0, HashMap::new()); // Nobody's watching.
+    let mut camera = GameCamera::new();
+    let theme = THEMES[0];
+    let mut current_time = 0.0_f64;
+    let mut result = MatchResult::default();
+
+    world_reset(
+        &mut world,
+        level,
+        &Options {
+            left: Controller::Ai,
+            right: Controller::Ai,
+            ..Default::default()
+        },
+    );
+    let paddles = world
+        .query::<(&Transform, &ControlType)>()
+        .iter()
+        .map(|(id, (transform, _control))| (id, Side::of(transform.position.0)))
+        .collect::<Vec<_>>();
+    for (id, side) in paddles {
+        let brain = match side {
+            Side::Left => options.left.1,
+            Side::Right => options.right.1,
+        };
+        world.insert_one(id, brain).unwrap();
+    }
+
+    'rounds: loop {
+        start_round(&mut world, &mut game_state, current_time);
+        let mut match_stats = MatchStats::new(game_state.mode.label(), &level.name);
+        let started_at = current_time;
+        while game_state.phase == Phase::Ongoing {
+            if current_time - started_at > options.time_limit {
+                break 'rounds;
+            }
+            current_time += STEP as f64;
+            camera.update(STEP);
+            if camera.frozen() {
+                continue;
+            }
+            let mut frame = Frame {
+                current_time,
+                delta: STEP,
+                level,
+                theme: &theme,
+                particles: &mut particles,
+                camera: &mut camera,
+                match_stats: &mut match_stats,
+                trail_tint: None,
+                typing: false,
+                events: Vec::new(),
+                sounds: Vec::new(),
+            };
+            step_physics(&mut world, &mut game_state, &mut frame);
+        }
+        result.rallies.push(match_stats.longest_rally);
+        // The same first-to-however-many as the game.
+        result.winner = game_state.record_round();
+        if result.winner.is_some() {
+            break;
+        }
+    }
+    result.left_score = game_state.left_rounds;
+    result.right_score = game_state.right_rounds;
+    result.duration = current_time;
+    result
+}
+
+fn print_csv(results: &[MatchResult]) {
+    println!("match,winner,left_score,right_score,rounds,average_rally,longest_rally,duration");
+    for (i, result) in results.iter().enumerate() {
+        println!(
+            "{},{},{},{},{},{},{},{}",
+            i + 1,
+            result.winner.map_or("none", |winner| winner.label()),
+            result.left_score,
+            result.right_score,
+            result.rallies.len(),
+            result.average_rally(),
+            result.longest_rally(),
+            result.duration
+        );
+    }
+}
+
+fn print_json(options: &SimOptions, results: &[MatchResult], summary: &Summary) {
+    let scores = summary
+        .scores
+        .iter()
+        .map(|(score, count)| format!("{}: {}", json_string(score), count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let matches = results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            format!(
+                "    {{\"match\": {}, \"winner\": {}, \"left_score\": {}, \"right_score\": {}, \"rounds\": {}, \"average_rally\": {}, \"longest_rally\": {}, \"duration\": {}}}",
+                i + 1,
+                result
+                    .winner
+                    .map_or("null".to_string(), |winner| json_string(winner.label())),
+                result.left_score,
+                result.right_score,
+                result.rallies.len(),
+                result.average_rally(),
+                result.longest_rally(),
+                result.duration
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    println!(
+        "{{\n  \"left\": {},\n  \"right\": {},\n  \"mode\": {},\n  \"seed\": {},\n  \"matches\": {},\n  \"left_win_rate\": {},\n  \"right_win_rate\": {},\n  \"called_off\": {},\n  \"average_rally\": {},\n  \"scores\": {{{}}},\n  \"results\": [\n{}\n  ]\n}}",
+        json_string(&options.left.0),
+        json_string(&options.right.0),
+        json_string(options.mode.label()),
+        options.seed,
+        results.len(),
+        summary.left_win_rate,
+        summary.right_win_rate,
+        summary.called_off,
+        summary.average_rally,
+        scores,
+        matches
+    );
+}
+
+// The batch as a whole.
+struct Summary {
+    left_win_rate: f32,
+    right_win_rate: f32,
+    called_off: usize,
+    average_rally: f32,              // Over every round played.
+    scores: BTreeMap<String, usize>, // Final scores, like "5-3", and how often they came up.
+}
+
+impl Summary {
+    fn new(results: &[MatchResult]) -> Summary {
+        let share = |side: Side| {
+            results
+                .iter()
+                .filter(|result| result.winner == Some(side))
+                .count() as f32
+                / results.len().max(1) as f32
+        };
+        let rallies = results
+            .iter()
+            .flat_map(|result| result.rallies.iter())
+            .collect::<Vec<_>>();
+        let mut scores = BTreeMap::new();
+        for result in results.iter().filter(|result| result.winner.is_some()) {
+            *scores
+                .entry(format!("{}-{}", result.left_score, result.right_score))
+                .or_insert(0) += 1;
+        }
+        Summary {
+            left_win_rate: share(Side::Left),
+            right_win_rate: share(Side::Right),
+            called_off: results
+                .iter()
+                .filter(|result| result.winner.is_none())
+                .count(),
+            average_rally: rallies.iter().copied().sum::<u32>() as f32
+                / rallies.len().max(1) as f32,
+            scores,
+        }
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = SimOptions::parse(&args).unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, HELP);
+        std::process::exit(2);
+    });
+
+    // There's no window, so the arena needs a size of its own.
+    fix_arena_size(ARENA_SIZE.0, ARENA_SIZE.1);
+    rand::srand(options.seed);
+    let level = match &options.level {
+        Some(path) => Level::load_or_default(path),
+        None => Level::default(),
+    };
+
+    let results = (0..options.matches)
+        .map(|_| play_match(&options, &level))
+        .collect::<Vec<_>>();
+    let summary = Summary::new(&results);
+    match options.format {
+        Format::Csv => {
+            print_csv(&results);
+            eprintln!(
+                "{} ({}) vs {} ({}): left won {:.1}%, right won {:.1}%, {} called off, average rally {:.2}",
+                options.left.0,
+                Side::Left.label(),
+                options.right.0,
+                Side::Right.label(),
+                summary.left_win_rate * 100.0,
+                summary.right_win_rate * 100.0,
+                summary.called_off,
+                summary.average_rally
+            );
+        }
+        Format::Json => print_json(&options, &results, &summary),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(left: &str, right: &str, mode: GameMode) -> SimOptions {
+        SimOptions {
+            points: 2,
+            left: (left.to_string(), Brain::parse(left).unwrap()),
+            right: (right.to_string(), Brain::parse(right).unwrap()),
+            mode,
+            time_limit: 60.0,
+            ..Default::default()
+        }
+    }
+
+    // Shooting brains used to leave every bullet they'd ever fired lying around, and could despawn
+    // one twice when it hit two things at once.
+    #[test]
+    fn shooting_brains_play_matches_out() {
+        fix_arena_size(ARENA_SIZE.0, ARENA_SIZE.1);
+        let level = Level::default();
+        for (seed, (left, right, mode)) in [
+            ("gunner", "ace", GameMode::Classic),
+            ("ace", "ace", GameMode::Classic),
+            ("ace", "ace", GameMode::Multiball),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            rand::srand(seed as u64 + 2);
+            let result = play_match(&options(left, right, mode), &level);
+            if let Some(winner) = result.winner {
+                let (won, lost) = match winner {
+                    Side::Left => (result.left_score, result.right_score),
+                    Side::Right => (result.right_score, result.left_score),
+                };
+                assert_eq!(won, 2);
+                assert!(lost < 2);
+            }
+            // A rally for every round that got finished. (Long ones can still get called off.)
+            assert_eq!(
+                result.rallies.len() as i32,
+                result.left_score + result.right_score
+            );
+            assert!(result.duration > 0.0);
+        }
+    }
+}
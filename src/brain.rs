@@ -0,0 +1,76 @@
+// How an AI paddle plays. Paddles without one play like the classic AI.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Brain {
+    pub tracking: f32, // How hard it steers after the ball. (1 is the classic AI.)
+    pub fire_cooldown: Option<f64>, // Seconds between shots, if it shoots at all.
+}
+
+impl Default for Brain {
+    fn default() -> Self {
+        Brain {
+            tracking: 1.0,
+            fire_cooldown: None,
+        }
+    }
+}
+
+// A few ready-made brains, for pitting against each other.
+pub const BRAIN_PRESETS: [(&str, Brain); 4] = [
+    (
+        "classic",
+        Brain {
+            tracking: 1.0,
+            fire_cooldown: None,
+        },
+    ),
+    (
+        "gunner",
+        Brain {
+            tracking: 1.0,
+            fire_cooldown: Some(1.0),
+        },
+    ),
+    (
+        "tracker",
+        Brain {
+            tracking: 1.5,
+            fire_cooldown: None,
+        },
+    ),
+    (
+        "ace",
+        Brain {
+            tracking: 1.5,
+            fire_cooldown: Some(0.6),
+        },
+    ),
+];
+
+impl Brain {
+    // Either a preset's name, or a tracking and cooldown like 1.2:0.8 (or 1.2:none.)
+    pub fn parse(text: &str) -> Result<Brain, String> {
+        if let Some((_name, brain)) = BRAIN_PRESETS.iter().find(|(name, _)| *name == text) {
+            return Ok(*brain);
+        }
+        let (tracking, cooldown) = text
+            .split_once(':')
+            .ok_or(format!("unknown brain '{}'", text))?;
+        let tracking = tracking
+            .parse::<f32>()
+            .map_err(|_| format!("'{}' isn't a tracking strength", tracking))?;
+        let fire_cooldown = match cooldown {
+            "none" => None,
+            cooldown => Some(
+                cooldown
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|cooldown| *cooldown > 0.0)
+                    .ok_or(format!("'{}' isn't a cooldown", cooldown))?,
+            ),
+        };
+        Ok(Brain {
+            tracking,
+            fire_cooldown,
+        })
+    }
+}
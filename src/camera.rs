@@ -0,0 +1,125 @@
+use macroquad::prelude::*;
+
+use crate::theme::REDUCED_MOTION;
+
+// How far and how much the screen can be thrown around at full trauma.
+const MAX_SHAKE_OFFSET: f32 = 24.0;
+const MAX_SHAKE_ANGLE: f32 = 3.0; // Degrees.
+const SHAKE_SPEED: f32 = 20.0; // How quickly the noise moves along.
+
+// How quickly things settle back down, per second.
+const TRAUMA_DECAY: f32 = 1.5;
+const PUNCH_DECAY: f32 = 6.0;
+
+// The most the game can be frozen for at once.
+const MAX_HIT_STOP: f32 = 0.5;
+
+// Hashing a lattice point to a gradient between -1 and 1.
+fn lattice(point: i32, seed: u32) -> f32 {
+    let mut x = (point as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x2c1b_3c6d);
+    x ^= x >> 12;
+    x = x.wrapping_mul(0x297a_2d39);
+    x ^= x >> 15;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// One-dimensional gradient noise, smoothly wandering between about -1 and 1.
+pub fn noise(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let f = x - cell;
+    let from = lattice(cell as i32, seed) * f;
+    let to = lattice(cell as i32 + 1, seed) * (f - 1.0);
+    let t = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    ((from + (to - from) * t) * 2.0).clamp(-1.0, 1.0)
+}
+
+// The view onto the arena. Hits add trauma, which shakes the screen and wears off over time, while
+// the hit-stop timer freezes gameplay separately.
+#[derive(Default, Clone, Copy)]
+pub struct GameCamera {
+    trauma: f32,   // From 0 to 1.
+    punch: f32,    // Extra zoom, on top of the usual.
+    hit_stop: f32, // Seconds of frozen gameplay left.
+    time: f32,     // For moving along the noise.
+    pub reduced_motion: bool,
+}
+
+impl GameCamera {
+    pub fn new() -> Self {
+        GameCamera::default()
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    // A quick zoom in that eases back out.
+    pub fn punch(&mut self, amount: f32) {
+        self.punch = self.punch.max(amount);
+    }
+
+    pub fn hit_stop(&mut self, seconds: f32) {
+        self.hit_stop = (self.hit_stop.max(0.0) + seconds).min(MAX_HIT_STOP);
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.hit_stop > 0.0
+    }
+
+    // How much of the motion actually makes it to the screen.
+    fn motion(&self) -> f32 {
+        if self.reduced_motion {
+            REDUCED_MOTION
+        } else {
+            1.0
+        }
+    }
+
+    // How hard the screen's shaking, from 0 to 1. Squared so small hits stay subtle.
+    pub fn shake(&self) -> f32 {
+        self.trauma * self.trauma * self.motion()
+    }
+
+    // A little flash of the background while the game's frozen.
+    pub fn flash(&self) -> f32 {
+        (self.hit_stop * 6.0).clamp(0.0, 0.1) * self.motion()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        self.punch *= (-PUNCH_DECAY * dt).exp();
+        self.hit_stop = (self.hit_stop - dt).max(0.0);
+    }
+
+    // Where a spot on the screen (like the mouse) is in the arena, with the shake and zoom undone.
+    pub fn screen_to_world(&self, point: (f32, f32)) -> (f32, f32) {
+        // The on-screen camera, since render targets get flipped back over on the way out.
+        let world = self.scene(None).screen_to_world(vec2(point.0, point.1));
+        (world.x, world.y)
+    }
+
+    // The camera for this frame, drawing to the screen or into `render_target`.
+    pub fn scene(&self, render_target: Option<RenderTarget>) -> Camera2D {
+        let shake = self.shake();
+        let at = self.time * SHAKE_SPEED;
+        let zoom = 1.0 + self.punch * self.motion();
+        // Render targets come out flipped, so they're drawn into the other way up.
+        let flip = if render_target.is_some() { 1.0 } else { -1.0 };
+        Camera2D {
+            target: vec2(
+                screen_width() / 2.0 + noise(at, 1) * MAX_SHAKE_OFFSET * shake,
+                screen_height() / 2.0 + noise(at, 2) * MAX_SHAKE_OFFSET * shake,
+            ),
+            rotation: noise(at, 3) * MAX_SHAKE_ANGLE * shake,
+            zoom: vec2(
+                2.0 / screen_width() * zoom,
+                2.0 / screen_height() * zoom * flip,
+            ),
+            render_target,
+            ..Default::default()
+        }
+    }
+}
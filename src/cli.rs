@@ -0,0 +1,377 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::modes::GameMode;
+
+pub const HELP: &str = "Pong with Guns
+
+Usage: cmps-455-2-pong-with-guns [options]
+
+Options:
+  --windowed              Play in a window instead of fullscreen.
+  --resolution <W>x<H>    Window size, like 1280x720. (Implies --windowed.)
+  --seed <number>         Seed the random number generator, for repeatable games.
+  --mode <mode>           Start in classic, multiball or survival.
+  --left <ai|human>       Who plays the left paddle. (Default: human.)
+  --right <ai|human>      Who plays the right paddle. (Default: ai.)
+  --mute                  No music or sound effects.
+  --config <path>         Read options from a file, one per line without the dashes.
+                          Anything on the command line wins over the file.
+  --help                  Show this and quit.
+";
+
+// Who's in charge of a paddle.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Controller {
+    Ai,
+    Human,
+}
+
+impl Controller {
+    fn parse(word: &str) -> Result<Controller, String> {
+        match word {
+            "ai" => Ok(Controller::Ai),
+            "human" => Ok(Controller::Human),
+            other => Err(format!("expected ai or human, found '{}'", other)),
+        }
+    }
+}
+
+// Everything that can be set when launching the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub windowed: bool,
+    pub resolution: Option<(i32, i32)>,
+    pub seed: Option<u64>,
+    pub mode: Option<GameMode>,
+    pub left: Controller,
+    pub right: Controller,
+    pub mute: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            windowed: false,
+            resolution: None,
+            seed: None,
+            mode: None,
+            left: Controller::Human,
+            right: Controller::Ai,
+            mute: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    // Reading options out of a list of arguments (without the program name), on top of whatever's
+    // already been set. Config files get read as they come up.
+    pub fn parse(&mut self, args: &[String]) -> Result<(), String> {
+        self.parse_within(args, &mut Vec::new())
+    }
+
+    // The same, keeping track of the config files it's part way through, so one that (eventually)
+    // reads itself again is an error rather than going round forever.
+    fn parse_within(&mut self, args: &[String], loading: &mut Vec<PathBuf>) -> Result<(), String> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                return Err(format!("unexpected argument '{}'", arg));
+            };
+            let mut value = || {
+                args.next()
+                    .ok_or(format!("--{} needs a value", flag))
+                    .map(|value| value.as_str())
+            };
+            match flag {
+                "windowed" => self.windowed = true,
+                "mute" => self.mute = true,
+                "help" => self.help = true,
+                "resolution" => {
+                    let value = value()?;
+                    let parsed = value.split_once('x').and_then(|(width, height)| {
+                        Some((width.parse::<i32>().ok()?, height.parse::<i32>().ok()?))
+                    });
+                    match parsed {
+                        Some((width, height)) if width > 0 && height > 0 => {
+                            self.resolution = Some((width, height));
+                            self.windowed = true;
+                        }
+                        _ => return Err(format!("'{}' isn't a resolution like 1280x720", value)),
+                    }
+                }
+                "seed" => {
+                    let value = value()?;
+                    self.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("'{}' isn't a seed", value))?,
+                    );
+                }
+                "mode" => {
+                    let value = value()?;
+                    self.mode = Some(
+                        GameMode::ALL
+                            .into_iter()
+                            .find(|mode| mode.label().eq_ignore_ascii_case(value))
+                            .ok_or(format!("unknown mode '{}'", value))?,
+                    );
+                }
+                "left" => self.left = Controller::parse(value()?)?,
+                "right" => self.right = Controller::parse(value()?)?,
+                "config" => {
+                    let path = value()?;
+                    let source = std::fs::read_to_string(path)
+                        .map_err(|error| format!("{}: {}", path, error))?;
+                    // (The same file can be reached by different paths.)
+                    let canonical = Path::new(path)
+                        .canonicalize()
+                        .unwrap_or_else(|_| PathBuf::from(path));
+                    if loading.contains(&canonical) {
+                        return Err(format!("{}: config files can't include themselves", path));
+                    }
+                    loading.push(canonical);
+                    let result = self.parse_within(&config_args(&source), loading);
+                    loading.pop();
+                    result.map_err(|error| format!("{}: {}", path, error))?;
+                }
+                other => return Err(format!("unknown option '--{}'", other)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Turning a config file into arguments, so it reads just like the command line.
+fn config_args(source: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    for line in source.lines() {
+        let text = line.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        let (flag, value) = text.split_once(' ').unwrap_or((text, ""));
+        args.push(format!("--{}", flag));
+        if !value.trim().is_empty() {
+            args.push(value.trim().to_string());
+        }
+    }
+    args
+}
+
+// Options from a whole command line. Config files go first, so the command line can override them.
+pub fn options_from(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    // Picking out any config files first.
+    let mut configs = Vec::new();
+    let mut rest = Vec::new();
+    let mut iter = args.iter().cloned();
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            configs.push(arg);
+            configs.extend(iter.next());
+        } else {
+            rest.push(arg);
+        }
+    }
+    options.parse(&configs)?;
+    options.parse(&rest)?;
+    Ok(options)
+}
+
+// The options the game was launched with. Asking for help, or getting something wrong, quits
+// straight away.
+pub fn launch_options() -> &'static Options {
+    static OPTIONS: OnceLock<Options> = OnceLock::new();
+    OPTIONS.get_or_init(|| {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        let options = options_from(&args).unwrap_or_else(|error| {
+            eprintln!("{}\n\n{}", error, HELP);
+            std::process::exit(2);
+        });
+        if options.help {
+            print!("{}", HELP);
+            std::process::exit(0);
+        }
+        options
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    // A config file of its own for each test, since they run side by side.
+    fn config(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("pwg-{}-{}.cfg", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn configs_that_include_themselves() {
+        let path = config("itself", "");
+        std::fs::write(&path, format!("seed 3\nconfig {}\n", path.display())).unwrap();
+        let result = Options::default().parse(&args(&format!("--config {}", path.display())));
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().contains("can't include themselves"));
+    }
+
+    #[test]
+    fn configs_that_include_each_other() {
+        let (first, second) = (config("first", ""), config("second", ""));
+        std::fs::write(&first, format!("config {}\n", second.display())).unwrap();
+        std::fs::write(&second, format!("config {}\n", first.display())).unwrap();
+        let result = Options::default().parse(&args(&format!("--config {}", first.display())));
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+        assert!(result.unwrap_err().contains("can't include themselves"));
+    }
+
+    #[test]
+    fn the_same_config_can_be_read_twice_in_a_row() {
+        let path = config("twice", "mute\n");
+        let mut options = Options::default();
+        let result = options.parse(&args(&format!(
+            "--config {} --config {}",
+            path.display(),
+            path.display()
+        )));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(()));
+        assert!(options.mute);
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let options = options_from(&args(
+            "--resolution 800x600 --seed 42 --mode MultiBall --left ai --right human --mute --help",
+        ))
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                windowed: true,
+                resolution: Some((800, 600)),
+                seed: Some(42),
+                mode: Some(GameMode::Multiball),
+                left: Controller::Ai,
+                right: Controller::Human,
+                mute: true,
+                help: true,
+            }
+        );
+        assert_eq!(options_from(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn flags_missing_values() {
+        for flag in ["resolution", "seed", "mode", "left", "right", "config"] {
+            assert_eq!(
+                options_from(&args(&format!("--windowed --{}", flag))),
+                Err(format!("--{} needs a value", flag))
+            );
+        }
+    }
+
+    #[test]
+    fn bad_resolutions() {
+        for resolution in ["1280", "1280x", "x720", "0x720", "1280x-1", "widexhigh"] {
+            assert_eq!(
+                options_from(&args(&format!("--resolution {}", resolution))),
+                Err(format!("'{}' isn't a resolution like 1280x720", resolution))
+            );
+        }
+    }
+
+    #[test]
+    fn bad_values() {
+        assert_eq!(
+            options_from(&args("--seed soon")),
+            Err("'soon' isn't a seed".to_string())
+        );
+        assert_eq!(
+            options_from(&args("--mode golf")),
+            Err("unknown mode 'golf'".to_string())
+        );
+        assert_eq!(
+            options_from(&args("--left robot")),
+            Err("expected ai or human, found 'robot'".to_string())
+        );
+        assert_eq!(
+            options_from(&args("--fast")),
+            Err("unknown option '--fast'".to_string())
+        );
+        assert_eq!(
+            options_from(&args("windowed")),
+            Err("unexpected argument 'windowed'".to_string())
+        );
+    }
+
+    #[test]
+    fn controllers() {
+        assert_eq!(Controller::parse("ai"), Ok(Controller::Ai));
+        assert_eq!(Controller::parse("human"), Ok(Controller::Human));
+        assert!(Controller::parse("AI").is_err());
+        assert!(Controller::parse("").is_err());
+    }
+
+    #[test]
+    fn config_comments_and_blank_lines() {
+        let source =
+            "# The whole line.\n\nwindowed # After a flag.\n  seed 4  \nmode   survival\n#mute\n";
+        assert_eq!(
+            config_args(source),
+            args("--windowed --seed 4 --mode survival")
+        );
+    }
+
+    #[test]
+    fn command_line_wins_over_configs() {
+        let path = config("wins", "seed 1\nleft ai\nmode survival\n");
+        // Wherever the config comes on the line.
+        let before = options_from(&args(&format!("--config {} --seed 2", path.display())));
+        let after = options_from(&args(&format!("--seed 2 --config {}", path.display())));
+        std::fs::remove_file(&path).unwrap();
+        for options in [before.unwrap(), after.unwrap()] {
+            assert_eq!(options.seed, Some(2));
+            // And anything the command line leaves alone comes from the config.
+            assert_eq!(options.left, Controller::Ai);
+            assert_eq!(options.mode, Some(GameMode::Survival));
+        }
+    }
+
+    #[test]
+    fn configs_win_over_what_came_before_them() {
+        // Inside a config (or a plain `parse`), it's just whatever comes last.
+        let path = config("later", "seed 1\n");
+        let mut options = Options::default();
+        options
+            .parse(&args(&format!("--seed 2 --config {}", path.display())))
+            .unwrap();
+        assert_eq!(options.seed, Some(1));
+        options
+            .parse(&args(&format!("--config {} --seed 3", path.display())))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(options.seed, Some(3));
+    }
+
+    #[test]
+    fn config_errors_say_which_file() {
+        let path = config("broken", "seed soon\n");
+        let result = options_from(&args(&format!("--config {}", path.display())));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            result,
+            Err(format!("{}: 'soon' isn't a seed", path.display()))
+        );
+        assert!(options_from(&args("--config /nowhere/at/all.cfg")).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use macroquad::prelude::*;
+
+use crate::*;
+
+// The slow-mo settings, cycled through with a key.
+pub const TIME_SCALES: [f32; 3] = [1.0, 0.5, 0.25];
+
+// The game's own clock, which runs at the game's time scale and stops when it's paused. Gameplay
+// (cooldowns, effects, regeneration, movers) goes by this, and menus go by the real one.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct GameClock {
+    pub paused: bool,
+    advancing: bool, // Running just this one frame, while paused.
+    time: f64,
+    delta: f32,
+}
+
+impl GameClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The hotkeys, and moving along by however long the frame took (scaled.)
+    pub fn update(&mut self, game_state: &mut GameState, frame_time: f32) {
+        if is_key_pressed(KeyCode::F5) {
+            self.paused = !self.paused;
+        }
+        // Stepping forward pauses first, so it's always exactly one frame.
+        self.advancing = is_key_pressed(KeyCode::F6);
+        if self.advancing {
+            self.paused = true;
+        }
+        if is_key_pressed(KeyCode::F7) {
+            let next = TIME_SCALES
+                .iter()
+                .position(|&scale| scale == game_state.time_scale)
+                .map_or(0, |index| (index + 1) % TIME_SCALES.len());
+            game_state.time_scale = TIME_SCALES[next];
+        }
+        self.delta = if self.running() {
+            frame_time * game_state.time_scale
+        } else {
+            0.0
+        };
+        self.time += self.delta as f64;
+    }
+
+    // Whether the game moves on this frame.
+    pub fn running(&self) -> bool {
+        !self.paused || self.advancing
+    }
+
+    pub fn now(&self) -> f64 {
+        self.time
+    }
+
+    // This frame's (scaled) length.
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    // What's up with time, for the corner of the screen. Nothing if it's running normally.
+    pub fn draw(&self, time_scale: f32, hud: &Hud, theme: &Theme) {
+        let text = match (self.paused, time_scale) {
+            (true, _) => "PAUSED  [F5] resume  [F6] step".to_string(),
+            (false, scale) if scale != 1.0 => format!("x{}  [F7] speed", scale),
+            _ => return,
+        };
+        hud.text(
+            &text,
+            Anchor::BottomRight,
+            (-32.0, -72.0),
+            20.0,
+            theme.warning,
+        );
+    }
+}
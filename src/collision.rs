@@ -0,0 +1,444 @@
+use crate::{Ball, Bounds, Transform};
+
+// The shapes anything in the arena can collide as, relative to its position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+    Segment { half: (f32, f32) }, // From the middle out to one end.
+    Capsule { half: (f32, f32), radius: f32 },
+}
+
+impl Shape {
+    // The two ends of the core segment, in world space. (Circles are just a point.)
+    pub fn endpoints(&self, position: (f32, f32)) -> ((f32, f32), (f32, f32)) {
+        match self {
+            Shape::Circle { .. } => (position, position),
+            Shape::Segment { half } | Shape::Capsule { half, .. } => (
+                (position.0 - half.0, position.1 - half.1),
+                (position.0 + half.0, position.1 + half.1),
+            ),
+        }
+    }
+
+    // How far the surface sits from the core segment.
+    pub fn radius(&self) -> f32 {
+        match self {
+            Shape::Circle { radius } | Shape::Capsule { radius, .. } => *radius,
+            Shape::Segment { .. } => 0.0,
+        }
+    }
+}
+
+// Where and how deep a circle is poking into something.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub normal: (f32, f32), // Pointing out of the shape, towards the circle.
+    pub depth: f32,
+}
+
+pub fn square_distance(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    (x1 - x2).powf(2.0) + (y1 - y2).powf(2.0)
+}
+
+// Returns the squared distance between point c and segment ab
+pub fn square_distance_point_segment(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    let ab = (b.0 - a.0, b.1 - a.1); // Getting our distance vectors.
+    let ac = (c.0 - a.0, c.1 - a.1);
+    let bc = (c.0 - b.0, c.1 - b.1);
+    let e = ac.0 * ab.0 + ac.1 * ab.1; // Getting the dot product for the central thingy.
+    if e <= 0.0 {
+        return ac.0 * ac.0 + ac.1 * ac.1;
+    } // Handle cases where c projects outside ab
+    let f = ab.0 * ab.0 + ab.1 * ab.1;
+    if e >= f {
+        return bc.0 * bc.0 + bc.1 * bc.1;
+    } // Handle cases where c projects onto ab
+    ((ac.0 * ac.0 + ac.1 * ac.1) - e * e / f).max(0.0) // (Rounding can dip just under zero.)
+}
+
+// Whether a ball is touching a paddle, going by the paddle's shape.
+pub fn test_sphere_capsule(sphere: (&Transform, &Ball), capsule: (&Transform, &Bounds)) -> bool {
+    let shape = capsule.1.shape();
+    let (a, b) = shape.endpoints(capsule.0.position);
+    // Compute (squared) distance between sphere center and capsule line segment
+    let dist2 = square_distance_point_segment(a, b, sphere.0.position);
+    // If (squared) distance smaller than (squared) sum of radii, they collide
+    dist2 <= (sphere.1.radius + shape.radius()).powf(2.0)
+}
+
+// Returns the point on segment ab closest to point c.
+pub fn closest_point_segment(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> (f32, f32) {
+    let ab = (b.0 - a.0, b.1 - a.1);
+    let f = ab.0 * ab.0 + ab.1 * ab.1;
+    if f == 0.0 {
+        return a;
+    }
+    let t = (((c.0 - a.0) * ab.0 + (c.1 - a.1) * ab.1) / f).clamp(0.0, 1.0);
+    (a.0 + ab.0 * t, a.1 + ab.1 * t)
+}
+
+// Testing a circle against a shape, handing back the contact if they overlap.
+pub fn circle_contact(
+    shape: &Shape,
+    position: (f32, f32),
+    center: (f32, f32),
+    radius: f32,
+) -> Option<Contact> {
+    let (a, b) = shape.endpoints(position);
+    let reach = shape.radius() + radius;
+    // Measuring from the closest point itself, so the normal always comes out a unit long. (The
+    // shortcut in `square_distance_point_segment` can drift a little, far from the origin.)
+    let closest = closest_point_segment(a, b, center);
+    let dist2 = square_distance(closest.0, closest.1, center.0, center.1);
+    if dist2 > reach.powf(2.0) {
+        return None;
+    }
+    let distance = dist2.sqrt();
+    let normal = if distance > 0.0 {
+        (
+            (center.0 - closest.0) / distance,
+            (center.1 - closest.1) / distance,
+        )
+    } else {
+        (0.0, -1.0) // Dead center, so just pick a direction.
+    };
+    Some(Contact {
+        normal,
+        depth: reach - distance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A little xorshift, so the property tests are the same every run.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, low: f32, high: f32) -> f32 {
+            low + (high - low) * self.next()
+        }
+
+        fn point(&mut self) -> (f32, f32) {
+            (self.range(-500.0, 500.0), self.range(-500.0, 500.0))
+        }
+    }
+
+    const CASES: usize = 2000;
+    const SAMPLES: usize = 4000;
+
+    // The slow way: walking along the segment and keeping the closest sample.
+    fn brute_distance(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+        (0..=SAMPLES)
+            .map(|i| {
+                let t = i as f32 / SAMPLES as f32;
+                let point = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                square_distance(point.0, point.1, c.0, c.1).sqrt()
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    // How far off the samples can be from the real closest point.
+    fn sample_error(a: (f32, f32), b: (f32, f32)) -> f32 {
+        square_distance(a.0, a.1, b.0, b.1).sqrt() / SAMPLES as f32 + 0.01
+    }
+
+    fn ball(position: (f32, f32), radius: f32) -> (Transform, Ball) {
+        (
+            Transform {
+                position,
+                velocity: (0.0, 0.0),
+            },
+            Ball {
+                radius,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn paddle(position: (f32, f32), bounds: Bounds) -> (Transform, Bounds) {
+        (
+            Transform {
+                position,
+                velocity: (0.0, 0.0),
+            },
+            bounds,
+        )
+    }
+
+    fn touching(ball: &(Transform, Ball), paddle: &(Transform, Bounds)) -> bool {
+        test_sphere_capsule((&ball.0, &ball.1), (&paddle.0, &paddle.1))
+    }
+
+    #[test]
+    fn square_distance_known_values() {
+        assert_eq!(square_distance(0.0, 0.0, 3.0, 4.0), 25.0);
+        assert_eq!(square_distance(-1.0, 2.0, -1.0, 2.0), 0.0);
+        assert_eq!(square_distance(1.0, 1.0, -1.0, -1.0), 8.0);
+    }
+
+    #[test]
+    fn square_distance_is_symmetric_and_never_negative() {
+        let mut random = Random(1);
+        for _ in 0..CASES {
+            let (p, q) = (random.point(), random.point());
+            let there = square_distance(p.0, p.1, q.0, q.1);
+            assert!(there >= 0.0);
+            assert_eq!(there, square_distance(q.0, q.1, p.0, p.1));
+        }
+    }
+
+    #[test]
+    fn point_segment_known_values() {
+        let (a, b) = ((0.0, 0.0), (10.0, 0.0));
+        assert_eq!(square_distance_point_segment(a, b, (5.0, 3.0)), 9.0); // Over the middle.
+        assert_eq!(square_distance_point_segment(a, b, (-3.0, 4.0)), 25.0); // Past a.
+        assert_eq!(square_distance_point_segment(a, b, (13.0, -4.0)), 25.0); // Past b.
+        assert_eq!(square_distance_point_segment(a, b, (10.0, 0.0)), 0.0); // On an end.
+        assert_eq!(square_distance_point_segment(a, b, (4.0, 0.0)), 0.0); // On the segment.
+        assert_eq!(square_distance_point_segment(a, a, (3.0, 4.0)), 25.0); // Just a point.
+    }
+
+    #[test]
+    fn point_segment_matches_brute_force() {
+        let mut random = Random(2);
+        for _ in 0..CASES {
+            let (a, b, c) = (random.point(), random.point(), random.point());
+            let fast = square_distance_point_segment(a, b, c).sqrt();
+            let slow = brute_distance(a, b, c);
+            let error = sample_error(a, b);
+            assert!(
+                (fast - slow).abs() <= error,
+                "a {:?} b {:?} c {:?}: {} vs {}",
+                a,
+                b,
+                c,
+                fast,
+                slow
+            );
+            // And it doesn't care which way round the segment goes.
+            let reversed = square_distance_point_segment(b, a, c).sqrt();
+            assert!((fast - reversed).abs() <= error);
+        }
+    }
+
+    #[test]
+    fn sphere_capsule_matches_brute_force() {
+        let mut random = Random(3);
+        for _ in 0..CASES {
+            let bounds = Bounds(random.range(1.0, 40.0), random.range(1.0, 120.0));
+            let paddle = paddle(random.point(), bounds);
+            let radius = random.range(1.0, 40.0);
+            let (a, b) = bounds.shape().endpoints(paddle.0.position);
+            // Somewhere near enough to the paddle to be interesting.
+            let center = (
+                paddle.0.position.0 + random.range(-150.0, 150.0),
+                paddle.0.position.1 + random.range(-200.0, 200.0),
+            );
+            let gap = brute_distance(a, b, center) - (radius + bounds.shape().radius());
+            // Right on the edge, the samples can't say either way.
+            if gap.abs() <= sample_error(a, b) {
+                continue;
+            }
+            assert_eq!(
+                touching(&ball(center, radius), &paddle),
+                gap < 0.0,
+                "bounds ({}, {}) at {:?}, ball {} at {:?}",
+                bounds.0,
+                bounds.1,
+                paddle.0.position,
+                radius,
+                center
+            );
+        }
+    }
+
+    // The capsule reaches `bounds.0` out either side of its middle and `bounds.1` up and down, so a
+    // ball touching it straight on from any side should hit, and one just clear of it shouldn't.
+    #[test]
+    fn sphere_capsule_reaches_its_bounds() {
+        let mut random = Random(4);
+        for _ in 0..CASES {
+            let width = random.range(4.0, 40.0);
+            let bounds = Bounds(width, random.range(width, 120.0));
+            let paddle = paddle(random.point(), bounds);
+            let radius = random.range(1.0, 40.0);
+            let (x, y) = paddle.0.position;
+            for (offset, reach) in [
+                ((0.0, -1.0), bounds.1), // Top.
+                ((0.0, 1.0), bounds.1),  // Bottom.
+                ((-1.0, 0.0), bounds.0), // Left.
+                ((1.0, 0.0), bounds.0),  // Right.
+            ] {
+                let at = |distance: f32| (x + offset.0 * distance, y + offset.1 * distance);
+                assert!(
+                    touching(&ball(at(reach + radius - 0.05), radius), &paddle),
+                    "should touch: bounds ({}, {}), ball {}, side {:?}",
+                    bounds.0,
+                    bounds.1,
+                    radius,
+                    offset
+                );
+                assert!(
+                    !touching(&ball(at(reach + radius + 0.05), radius), &paddle),
+                    "shouldn't touch: bounds ({}, {}), ball {}, side {:?}",
+                    bounds.0,
+                    bounds.1,
+                    radius,
+                    offset
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn full_size_paddle_hitbox_reaches_its_ends() {
+        // A 16 radius ball resting on the top of a full-size paddle.
+        let paddle = paddle((100.0, 300.0), Bounds(16.0, 64.0));
+        assert!(touching(&ball((100.0, 300.0 - 64.0 - 15.0), 16.0), &paddle));
+        assert!(!touching(
+            &ball((100.0, 300.0 - 64.0 - 17.0), 16.0),
+            &paddle
+        ));
+    }
+
+    // Close enough, for anything that went through a square root.
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn closest_point_known_values() {
+        let (a, b) = ((0.0, 0.0), (10.0, 0.0));
+        assert_eq!(closest_point_segment(a, b, (5.0, 3.0)), (5.0, 0.0)); // Over the middle.
+        assert_eq!(closest_point_segment(a, b, (-3.0, 4.0)), a); // Past a.
+        assert_eq!(closest_point_segment(a, b, (13.0, -4.0)), b); // Past b.
+        assert_eq!(closest_point_segment(a, b, (4.0, 0.0)), (4.0, 0.0)); // On the segment.
+        assert_eq!(closest_point_segment(a, a, (3.0, 4.0)), a); // Just a point.
+                                                                // And off the axes.
+        assert!(near(
+            closest_point_segment((0.0, 0.0), (10.0, 10.0), (0.0, 10.0)),
+            (5.0, 5.0)
+        ));
+    }
+
+    #[test]
+    fn closest_point_agrees_with_the_distance() {
+        let mut random = Random(5);
+        for _ in 0..CASES {
+            let (a, b, c) = (random.point(), random.point(), random.point());
+            let closest = closest_point_segment(a, b, c);
+            let there = square_distance(closest.0, closest.1, c.0, c.1).sqrt();
+            let expected = brute_distance(a, b, c);
+            assert!(
+                (there - expected).abs() <= sample_error(a, b),
+                "a {:?} b {:?} c {:?}: {} vs {}",
+                a,
+                b,
+                c,
+                there,
+                expected
+            );
+            // And it's actually on the segment, inside the box the ends make.
+            assert!(closest.0 >= a.0.min(b.0) - 0.01 && closest.0 <= a.0.max(b.0) + 0.01);
+            assert!(closest.1 >= a.1.min(b.1) - 0.01 && closest.1 <= a.1.max(b.1) + 0.01);
+        }
+    }
+
+    #[test]
+    fn circle_contact_on_a_segment() {
+        let wall = Shape::Segment { half: (50.0, 0.0) };
+        // Poking 2 into it from above, over the middle.
+        let contact = circle_contact(&wall, (0.0, 0.0), (10.0, -8.0), 10.0).unwrap();
+        assert!(near(contact.normal, (0.0, -1.0)));
+        assert!((contact.depth - 2.0).abs() < 1e-4);
+        // From below, it points the other way.
+        let contact = circle_contact(&wall, (0.0, 0.0), (10.0, 8.0), 10.0).unwrap();
+        assert!(near(contact.normal, (0.0, 1.0)));
+        // Off the end, it points out of the end.
+        let contact = circle_contact(&wall, (0.0, 0.0), (55.0, 0.0), 10.0).unwrap();
+        assert!(near(contact.normal, (1.0, 0.0)));
+        assert!((contact.depth - 5.0).abs() < 1e-4);
+        // And clear of it.
+        assert_eq!(circle_contact(&wall, (0.0, 0.0), (10.0, -11.0), 10.0), None);
+        assert_eq!(circle_contact(&wall, (0.0, 0.0), (61.0, 0.0), 10.0), None);
+    }
+
+    #[test]
+    fn circle_contact_on_a_capsule() {
+        let paddle = Bounds(16.0, 64.0).shape();
+        let position = (100.0, 300.0);
+        // Into the flat side.
+        let contact = circle_contact(&paddle, position, (124.0, 300.0), 10.0).unwrap();
+        assert!(near(contact.normal, (1.0, 0.0)));
+        assert!((contact.depth - 2.0).abs() < 1e-4);
+        // Into the round end, at an angle.
+        let end = (100.0, 300.0 - 48.0);
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        let center = (end.0 - 20.0 * diagonal, end.1 - 20.0 * diagonal);
+        let contact = circle_contact(&paddle, position, center, 10.0).unwrap();
+        assert!(near(contact.normal, (-diagonal, -diagonal)));
+        assert!((contact.depth - 6.0).abs() < 1e-3);
+        // The corners of its box are cut off, so a ball there misses.
+        let corner = (100.0 + 16.0 + 3.0, 300.0 - 64.0 - 3.0);
+        assert_eq!(circle_contact(&paddle, position, corner, 4.0), None);
+        // And clear of it.
+        assert_eq!(
+            circle_contact(&paddle, position, (127.0, 300.0), 10.0),
+            None
+        );
+    }
+
+    #[test]
+    fn circle_contact_on_a_circle() {
+        let bumper = Shape::Circle { radius: 20.0 };
+        let contact = circle_contact(&bumper, (0.0, 0.0), (0.0, 25.0), 10.0).unwrap();
+        assert!(near(contact.normal, (0.0, 1.0)));
+        assert!((contact.depth - 5.0).abs() < 1e-4);
+        assert_eq!(circle_contact(&bumper, (0.0, 0.0), (31.0, 0.0), 10.0), None);
+    }
+
+    #[test]
+    fn circle_contact_dead_center_still_has_a_normal() {
+        let paddle = Bounds(16.0, 64.0).shape();
+        let contact = circle_contact(&paddle, (100.0, 300.0), (100.0, 300.0), 10.0).unwrap();
+        assert_eq!(contact.normal, (0.0, -1.0));
+        assert_eq!(contact.depth, 26.0);
+    }
+
+    #[test]
+    fn circle_contact_agrees_with_the_capsule_test() {
+        let mut random = Random(6);
+        for _ in 0..CASES {
+            let bounds = Bounds(random.range(1.0, 40.0), random.range(1.0, 120.0));
+            let paddle = paddle(random.point(), bounds);
+            let radius = random.range(1.0, 40.0);
+            let center = (
+                paddle.0.position.0 + random.range(-150.0, 150.0),
+                paddle.0.position.1 + random.range(-200.0, 200.0),
+            );
+            let shape = bounds.shape();
+            let contact = circle_contact(&shape, paddle.0.position, center, radius);
+            let (a, b) = shape.endpoints(paddle.0.position);
+            let gap = brute_distance(a, b, center) - (radius + shape.radius());
+            // Right on the edge, the two can round differently.
+            if gap.abs() > sample_error(a, b) {
+                assert_eq!(contact.is_some(), touching(&ball(center, radius), &paddle));
+            }
+            if let Some(contact) = contact {
+                // The normal's always a unit vector, and the depth never negative.
+                let length = (contact.normal.0.powi(2) + contact.normal.1.powi(2)).sqrt();
+                assert!((length - 1.0).abs() < 1e-3);
+                assert!(contact.depth >= 0.0);
+            }
+        }
+    }
+}
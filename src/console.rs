@@ -0,0 +1,528 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::*;
+
+// How many lines of output are kept, and how many are shown.
+const LOG_LENGTH: usize = 64;
+const SHOWN_LINES: usize = 12;
+
+// Every command, with how it's used.
+const USAGE: [(&str, &str); 9] = [
+    ("spawn", "spawn <ball|powerup>"),
+    ("set", "set speed <number>"),
+    (
+        "give",
+        "give <left|right> <grow|shield|multi|slow|reverse|rapid>",
+    ),
+    ("score", "score <left|right> <number>"),
+    ("timescale", "timescale <number>"),
+    ("god", "god <left|right>"),
+    ("reset", "reset"),
+    ("clear", "clear"),
+    ("help", "help"),
+];
+
+const SIDES: [&str; 2] = ["left", "right"];
+
+// A command, once it's been made sense of.
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Command {
+    SpawnBall,
+    SpawnPowerUp,
+    SetSpeed(f32),
+    Give(Side, PowerUpKind),
+    Score(Side, i32),
+    TimeScale(f32),
+    God(Side),
+    Reset,
+    Clear,
+    Help,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let usage = |name: &str| {
+            USAGE
+                .iter()
+                .find(|(command, _usage)| *command == name)
+                .map_or(String::new(), |(_command, usage)| {
+                    format!("usage: {}", usage)
+                })
+        };
+        let command = match words.as_slice() {
+            ["spawn", "ball"] => Command::SpawnBall,
+            ["spawn", "powerup"] => Command::SpawnPowerUp,
+            ["set", "speed", speed] => Command::SetSpeed(
+                speed
+                    .parse()
+                    .ok()
+                    .filter(|speed: &f32| *speed > 0.0)
+                    .ok_or(format!("'{}' isn't a speed", speed))?,
+            ),
+            ["give", side, kind] => Command::Give(
+                parse_side(side)?,
+                PowerUpKind::ALL
+                    .into_iter()
+                    .find(|power_up| power_up.label().eq_ignore_ascii_case(kind))
+                    .ok_or(format!("there's no '{}' power-up", kind))?,
+            ),
+            ["score", side, score] => Command::Score(
+                parse_side(side)?,
+                score
+                    .parse()
+                    .ok()
+                    .filter(|score| *score >= 0)
+                    .ok_or(format!("'{}' isn't a score", score))?,
+            ),
+            ["timescale", scale] => Command::TimeScale(
+                scale
+                    .parse()
+                    .ok()
+                    .filter(|scale: &f32| *scale > 0.0 && *scale <= 4.0)
+                    .ok_or(format!("'{}' isn't a time scale (over 0, up to 4)", scale))?,
+            ),
+            ["god", side] => Command::God(parse_side(side)?),
+            ["reset"] => Command::Reset,
+            ["clear"] => Command::Clear,
+            ["help"] => Command::Help,
+            [name, ..] if USAGE.iter().any(|(command, _usage)| command == name) => {
+                return Err(usage(name))
+            }
+            [name, ..] => return Err(format!("unknown command '{}', try help", name)),
+            [] => return Err(String::new()),
+        };
+        Ok(command)
+    }
+}
+
+fn parse_side(word: &str) -> Result<Side, String> {
+    match word {
+        "left" => Ok(Side::Left),
+        "right" => Ok(Side::Right),
+        other => Err(format!("'{}' isn't a side, it's left or right", other)),
+    }
+}
+
+// What could come next, given the words before it. (All lowercase, like the usage.)
+fn candidates(words: &[&str]) -> Vec<String> {
+    let list: Vec<&str> = match words {
+        [] => USAGE.iter().map(|(command, _usage)| *command).collect(),
+        ["spawn"] => vec!["ball", "powerup"],
+        ["set"] => vec!["speed"],
+        ["give" | "score" | "god"] => SIDES.to_vec(),
+        ["give", _side] => PowerUpKind::ALL
+            .iter()
+            .map(|power_up| power_up.label())
+            .collect(),
+        _ => Vec::new(),
+    };
+    list.into_iter()
+        .map(|candidate| candidate.to_ascii_lowercase())
+        .collect()
+}
+
+// The paddle on one side, if there is one.
+fn paddle(world: &World, side: Side) -> Result<Entity, String> {
+    paddle_sides(world)
+        .into_iter()
+        .find(|(_id, paddle_side)| *paddle_side == side)
+        .map(|(id, _side)| id)
+        .ok_or(format!("there's no {} paddle", side.label()))
+}
+
+// A drop-down console for poking at the game while it runs.
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    browsing: Option<usize>, // How far back through the history we are.
+    log: Vec<String>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            open: false,
+            input: String::new(),
+            history: Vec::new(),
+            browsing: None,
+            log: vec!["Type help for the commands.".to_string()],
+        }
+    }
+
+    // Opening and closing it, and taking typing while it's open.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        game_state: &mut GameState,
+        level: &Level,
+        options: &Options,
+        current_time: f64,
+    ) {
+        if is_key_pressed(KeyCode::GraveAccent) {
+            self.open = !self.open;
+            // Clearing out anything typed before now. (Including the backtick.)
+            while get_char_pressed().is_some() {}
+            return;
+        }
+        if !self.open {
+            return;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.open = false;
+            return;
+        }
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() && c != '`' {
+                self.input.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.input.pop();
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.complete();
+        }
+        if is_key_pressed(KeyCode::Up) && !self.history.is_empty() {
+            let index = self
+                .browsing
+                .map_or(self.history.len() - 1, |index| index.saturating_sub(1));
+            self.browsing = Some(index);
+            self.input = self.history[index].clone();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            if let Some(index) = self.browsing {
+                if index + 1 < self.history.len() {
+                    self.browsing = Some(index + 1);
+                    self.input = self.history[index + 1].clone();
+                } else {
+                    self.browsing = None;
+                    self.input.clear();
+                }
+            }
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            let line = std::mem::take(&mut self.input);
+            self.browsing = None;
+            if line.trim().is_empty() {
+                return;
+            }
+            if self.history.last() != Some(&line) {
+                self.history.push(line.clone());
+            }
+            self.print(format!("> {}", line));
+            let result = Command::parse(&line).and_then(|command| {
+                self.run(command, world, game_state, level, options, current_time)
+            });
+            match result {
+                Ok(message) | Err(message) if !message.is_empty() => self.print(message),
+                _ => {}
+            }
+        }
+    }
+
+    fn print(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_LENGTH {
+            self.log.remove(0);
+        }
+    }
+
+    // Filling in the word being typed, as far as everything it could be agrees.
+    fn complete(&mut self) {
+        let mut words = self.input.split_whitespace().collect::<Vec<_>>();
+        let partial = if self.input.is_empty() || self.input.ends_with(' ') {
+            ""
+        } else {
+            words.pop().unwrap_or("")
+        };
+        let partial = partial.to_ascii_lowercase();
+        let matches = candidates(&words)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(&partial))
+            .collect::<Vec<_>>();
+        let Some(first) = matches.first() else {
+            return;
+        };
+        let common = matches.iter().fold(first.len(), |length, candidate| {
+            first
+                .chars()
+                .zip(candidate.chars())
+                .take(length)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        let mut completed = words.join(" ");
+        if !completed.is_empty() {
+            completed.push(' ');
+        }
+        completed.push_str(&first[..common]);
+        if matches.len() == 1 {
+            completed.push(' ');
+        } else if common == partial.len() {
+            // Nothing more to fill in, so showing the options instead.
+            self.print(matches.join("  "));
+        }
+        self.input = completed;
+    }
+
+    fn run(
+        &mut self,
+        command: Command,
+        world: &mut World,
+        game_state: &mut GameState,
+        level: &Level,
+        options: &Options,
+        current_time: f64,
+    ) -> Result<String, String> {
+        let message = match command {
+            Command::SpawnBall => {
+                spawn_ball(world, random_serve_direction());
+                "Served another ball.".to_string()
+            }
+            Command::SpawnPowerUp => {
+                spawn_power_up(world);
+                "Dropped in a power-up.".to_string()
+            }
+            Command::SetSpeed(speed) => {
+                let mut count = 0;
+                for (_id, (transform, ball)) in world.query_mut::<(&mut Transform, &mut Ball)>() {
+                    ball.speed = speed;
+                    renormalise(transform, speed);
+                    count += 1;
+                }
+                if count == 0 {
+                    return Err("there aren't any balls out".to_string());
+                }
+                format!("Set {} ball(s) to speed {}.", count, speed)
+            }
+            Command::Give(side, kind) => {
+                if !grant_power_up(world, paddle(world, side)?, kind, current_time) {
+                    return Err(format!("the {} paddle can't take power-ups", side.label()));
+                }
+                format!("Gave {} to {}.", kind.label(), side.label())
+            }
+            Command::Score(side, score) => {
+                match side {
+                    Side::Left => game_state.left_score = score,
+                    Side::Right => game_state.right_score = score,
+                }
+                format!("Set {}'s score to {}.", side.label(), score)
+            }
+            Command::TimeScale(scale) => {
+                game_state.time_scale = scale;
+                format!("Time scale set to {}.", scale)
+            }
+            Command::God(side) => {
+                let paddle = paddle(world, side)?;
+                let mut health = world
+                    .get::<&mut PaddleHealth>(paddle)
+                    .map_err(|_| format!("the {} paddle can't take damage anyway", side.label()))?;
+                health.invulnerable = !health.invulnerable;
+                format!(
+                    "God mode {} for {}.",
+                    if health.invulnerable { "on" } else { "off" },
+                    side.label()
+                )
+            }
+            Command::Reset => {
+                world_reset(world, level, options);
+                // A fresh match, with the same settings.
+                *game_state = GameState {
+                    mode: game_state.mode,
+                    rules: game_state.rules,
+                    knockout: game_state.knockout,
+                    horizontal_movement: game_state.horizontal_movement,
+                    theme: game_state.theme,
+                    reduced_motion: game_state.reduced_motion,
+                    ..GameState::new()
+                };
+                "Back to the start.".to_string()
+            }
+            Command::Clear => {
+                self.log.clear();
+                String::new()
+            }
+            Command::Help => USAGE
+                .iter()
+                .map(|(_command, usage)| *usage)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        Ok(message)
+    }
+
+    pub fn draw(&self, hud: &Hud, theme: &Theme, current_time: f64) {
+        if !self.open {
+            return;
+        }
+        let height = 40.0 + SHOWN_LINES as f32 * 20.0;
+        hud.strip(
+            0.0,
+            height,
+            Color {
+                a: 0.9,
+                ..theme.shadow
+            },
+        );
+        let lines = self
+            .log
+            .iter()
+            .flat_map(|line| line.lines())
+            .collect::<Vec<_>>();
+        let shown = &lines[lines.len().saturating_sub(SHOWN_LINES)..];
+        for (i, line) in shown.iter().enumerate() {
+            hud.text(
+                line,
+                Anchor::TopLeft,
+                (16.0, 8.0 + i as f32 * 20.0),
+                18.0,
+                theme.muted,
+            );
+        }
+        let cursor = if (current_time * 2.0) % 2.0 < 1.0 {
+            "_"
+        } else {
+            ""
+        };
+        hud.text(
+            &format!("> {}{}", self.input, cursor),
+            Anchor::TopLeft,
+            (16.0, height - 28.0),
+            20.0,
+            theme.foreground,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the input line looks like after a tab, and anything it printed.
+    fn complete(input: &str) -> (String, Option<String>) {
+        let mut console = Console::new();
+        console.log.clear();
+        console.input = input.to_string();
+        console.complete();
+        (console.input, console.log.pop())
+    }
+
+    #[test]
+    fn parses_every_command() {
+        assert_eq!(Command::parse("spawn ball"), Ok(Command::SpawnBall));
+        assert_eq!(Command::parse("spawn powerup"), Ok(Command::SpawnPowerUp));
+        assert_eq!(Command::parse("set speed 7.5"), Ok(Command::SetSpeed(7.5)));
+        assert_eq!(
+            Command::parse("give left grow"),
+            Ok(Command::Give(Side::Left, PowerUpKind::Grow))
+        );
+        assert_eq!(
+            Command::parse("score right 3"),
+            Ok(Command::Score(Side::Right, 3))
+        );
+        assert_eq!(Command::parse("timescale 0.5"), Ok(Command::TimeScale(0.5)));
+        assert_eq!(Command::parse("god right"), Ok(Command::God(Side::Right)));
+        assert_eq!(Command::parse("  reset  "), Ok(Command::Reset));
+        assert_eq!(Command::parse("clear"), Ok(Command::Clear));
+        assert_eq!(Command::parse("help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn power_ups_go_by_any_case() {
+        for kind in ["RAPID", "rapid", "Rapid"] {
+            assert_eq!(
+                Command::parse(&format!("give right {}", kind)),
+                Ok(Command::Give(Side::Right, PowerUpKind::RapidFire))
+            );
+        }
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            Command::parse("set speed fast"),
+            Err("'fast' isn't a speed".to_string())
+        );
+        assert!(Command::parse("set speed -2").is_err());
+        assert!(Command::parse("score left -1").is_err());
+        assert!(Command::parse("timescale 0").is_err());
+        assert!(Command::parse("timescale 5").is_err());
+        assert_eq!(
+            Command::parse("god middle"),
+            Err("'middle' isn't a side, it's left or right".to_string())
+        );
+        assert_eq!(
+            Command::parse("give left laser"),
+            Err("there's no 'laser' power-up".to_string())
+        );
+    }
+
+    #[test]
+    fn known_commands_used_wrong_show_their_usage() {
+        assert_eq!(
+            Command::parse("spawn"),
+            Err("usage: spawn <ball|powerup>".to_string())
+        );
+        assert_eq!(Command::parse("reset now"), Err("usage: reset".to_string()));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        assert_eq!(
+            Command::parse("jump"),
+            Err("unknown command 'jump', try help".to_string())
+        );
+        assert_eq!(Command::parse("   "), Err(String::new()));
+    }
+
+    #[test]
+    fn completes_a_single_match() {
+        assert_eq!(complete("sp"), ("spawn ".to_string(), None));
+        assert_eq!(complete("spawn b"), ("spawn ball ".to_string(), None));
+        assert_eq!(complete("god r"), ("god right ".to_string(), None));
+    }
+
+    #[test]
+    fn completes_power_ups_whatever_the_case() {
+        assert_eq!(
+            complete("give left gr"),
+            ("give left grow ".to_string(), None)
+        );
+        assert_eq!(
+            complete("give left GR"),
+            ("give left grow ".to_string(), None)
+        );
+        assert_eq!(
+            complete("give left Ra"),
+            ("give left rapid ".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn lists_the_options_when_stuck() {
+        let (input, printed) = complete("s");
+        assert_eq!(input, "s");
+        assert_eq!(printed, Some("spawn  set  score".to_string()));
+        let (input, printed) = complete("give left r");
+        assert_eq!(input, "give left r");
+        assert_eq!(printed, Some("reverse  rapid".to_string()));
+        let (input, printed) = complete("");
+        assert_eq!(input, "");
+        assert_eq!(printed.unwrap().split("  ").count(), USAGE.len());
+    }
+
+    #[test]
+    fn nothing_to_complete() {
+        assert_eq!(complete("help "), ("help ".to_string(), None));
+        assert_eq!(complete("give left zz"), ("give left zz".to_string(), None));
+    }
+}
@@ -0,0 +1,566 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::*;
+
+// How many frames the timings are taken over.
+const FRAME_HISTORY: usize = 120;
+
+// How long the velocity arrows get drawn, per unit of speed.
+const VELOCITY_SCALE: f32 = 16.0;
+
+// How close a click has to be to pick something out for the inspector.
+const PICK_RADIUS: f32 = 48.0;
+
+// Something the inspector can tweak.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Field {
+    LeftScore,
+    RightScore,
+    Knockout,
+    HorizontalMovement,
+    Theme,
+    PositionX,
+    PositionY,
+    VelocityX,
+    VelocityY,
+    HalfWidth,
+    HalfHeight,
+    BallRadius,
+    BallSpeed,
+    BallSpin,
+    RegenRate,
+    Destroyed,
+    Tracking,
+}
+
+impl Field {
+    const GAME: [Field; 5] = [
+        Field::LeftScore,
+        Field::RightScore,
+        Field::Knockout,
+        Field::HorizontalMovement,
+        Field::Theme,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Field::LeftScore => "left score",
+            Field::RightScore => "right score",
+            Field::Knockout => "knockout",
+            Field::HorizontalMovement => "free move",
+            Field::Theme => "theme",
+            Field::PositionX => "position x",
+            Field::PositionY => "position y",
+            Field::VelocityX => "velocity x",
+            Field::VelocityY => "velocity y",
+            Field::HalfWidth => "half width",
+            Field::HalfHeight => "half height",
+            Field::BallRadius => "ball radius",
+            Field::BallSpeed => "ball speed",
+            Field::BallSpin => "ball spin",
+            Field::RegenRate => "regen rate",
+            Field::Destroyed => "destroyed",
+            Field::Tracking => "ai tracking",
+        }
+    }
+
+    // How far one press moves it. (Toggles and cycles ignore this.)
+    fn step(&self) -> f32 {
+        match self {
+            Field::PositionX | Field::PositionY => 8.0,
+            Field::VelocityX | Field::VelocityY => 0.5,
+            Field::HalfWidth | Field::HalfHeight => 4.0,
+            Field::BallRadius => 2.0,
+            Field::BallSpeed => 0.25,
+            Field::BallSpin => 0.01,
+            Field::RegenRate => 1.0,
+            Field::Tracking => 0.1,
+            _ => 1.0,
+        }
+    }
+}
+
+// The debug overlay: hitboxes, velocities and what the AI's after, drawn over the arena, plus
+// counts, timings and an inspector for poking at things mid-game.
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub inspecting: bool,
+    selected: Option<Entity>,
+    cursor: usize, // Which of the inspector's fields is picked.
+    frame_times: Vec<f32>,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        DebugOverlay {
+            visible: false,
+            inspecting: false,
+            selected: None,
+            cursor: 0,
+            frame_times: Vec::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    // The toggles, the timings and the inspector's controls, once a frame.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        game_state: &mut GameState,
+        camera: &GameCamera,
+        delta: f32,
+    ) {
+        if is_key_pressed(KeyCode::F3) {
+            self.visible = !self.visible;
+        }
+        if is_key_pressed(KeyCode::F4) {
+            self.inspecting = !self.inspecting;
+            self.visible |= self.inspecting;
+        }
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.remove(0);
+        }
+        self.frame_times.push(delta);
+
+        if !self.inspecting {
+            return;
+        }
+        if self.selected.is_some_and(|entity| !world.contains(entity)) {
+            self.selected = None;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            // Going by where the mouse is in the arena, wherever the shake has thrown the view.
+            self.selected = pick(world, camera.screen_to_world(mouse_position()));
+        }
+        let fields = self.fields(world);
+        self.cursor = self.cursor.min(fields.len() - 1);
+        if is_key_pressed(KeyCode::PageDown) {
+            self.cursor = (self.cursor + 1) % fields.len();
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.cursor = (self.cursor + fields.len() - 1) % fields.len();
+        }
+        let boost = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+            10.0
+        } else {
+            1.0
+        };
+        let steps = if is_key_pressed(KeyCode::RightBracket) {
+            boost
+        } else if is_key_pressed(KeyCode::LeftBracket) {
+            -boost
+        } else {
+            return;
+        };
+        adjust(fields[self.cursor], world, self.selected, game_state, steps);
+    }
+
+    // The game's fields, then whatever the selected entity has.
+    fn fields(&self, world: &World) -> Vec<Field> {
+        let mut fields = Field::GAME.to_vec();
+        let Some(entity) = self.selected else {
+            return fields;
+        };
+        let Ok(entity) = world.entity(entity) else {
+            return fields;
+        };
+        if entity.has::<Transform>() {
+            fields.extend([
+                Field::PositionX,
+                Field::PositionY,
+                Field::VelocityX,
+                Field::VelocityY,
+            ]);
+        }
+        if entity.has::<Bounds>() {
+            fields.extend([Field::HalfWidth, Field::HalfHeight]);
+        }
+        if entity.has::<Ball>() {
+            fields.extend([Field::BallRadius, Field::BallSpeed, Field::BallSpin]);
+        }
+        if entity.has::<PaddleHealth>() {
+            fields.extend([Field::RegenRate, Field::Destroyed]);
+        }
+        if entity.has::<ControlType>() {
+            fields.push(Field::Tracking);
+        }
+        fields
+    }
+
+    // Hitboxes, velocities and AI targets, in the arena. (So this goes under the scene camera.)
+    pub fn draw_world(&self, world: &World, theme: &Theme) {
+        if !self.visible {
+            return;
+        }
+        let collider = Color { a: 0.8, ..LIME };
+        for (_id, (transform, bounds)) in world.query::<(&Transform, &Bounds)>().iter() {
+            draw_shape(&bounds.shape(), transform.position, collider);
+        }
+        for (_id, (transform, ball)) in world.query::<(&Transform, &Ball)>().iter() {
+            draw_shape(
+                &Shape::Circle {
+                    radius: ball.radius,
+                },
+                transform.position,
+                collider,
+            );
+        }
+        for (_id, (transform, bullet)) in world.query::<(&Transform, &Bullet)>().iter() {
+            draw_shape(
+                &Shape::Circle {
+                    radius: bullet.radius,
+                },
+                transform.position,
+                collider,
+            );
+        }
+        for (_id, (transform, power_up)) in world.query::<(&Transform, &PowerUp)>().iter() {
+            draw_shape(
+                &Shape::Circle {
+                    radius: power_up.radius,
+                },
+                transform.position,
+                collider,
+            );
+        }
+        for (_id, (transform, collider)) in world.query::<(&Transform, &Collider)>().iter() {
+            draw_shape(&collider.0, transform.position, ORANGE);
+        }
+
+        // Which way everything's heading.
+        for (_id, transform) in world.query::<&Transform>().iter() {
+            let (x, y) = transform.position;
+            let (dx, dy) = transform.velocity;
+            if dx != 0.0 || dy != 0.0 {
+                draw_line(
+                    x,
+                    y,
+                    x + dx * VELOCITY_SCALE,
+                    y + dy * VELOCITY_SCALE,
+                    2.0,
+                    SKYBLUE,
+                );
+            }
+        }
+
+        // And what the AI is going after, with where it's steering to.
+        let balls = world
+            .query::<(&Transform, &Ball)>()
+            .iter()
+            .map(|(e, (&i, &b))| (e, i, b))
+            .collect::<Vec<_>>();
+        for (_id, (transform, control)) in world.query::<(&Transform, &ControlType)>().iter() {
+            if !matches!(control, ControlType::AI(..)) {
+                continue;
+            }
+            if let Some(((_ball, target, _), _distance)) = ai_target(&balls, transform.position) {
+                let (x, y) = transform.position;
+                draw_line(x, y, target.position.0, target.position.1, 1.0, MAGENTA);
+                draw_line(
+                    x - 24.0,
+                    target.position.1,
+                    x + 24.0,
+                    target.position.1,
+                    3.0,
+                    MAGENTA,
+                );
+            }
+        }
+
+        // Marking out whatever's being inspected.
+        if let Some(transform) = self
+            .selected
+            .and_then(|entity| world.get::<&Transform>(entity).ok())
+        {
+            draw_circle_lines(
+                transform.position.0,
+                transform.position.1,
+                PICK_RADIUS,
+                2.0,
+                theme.warning,
+            );
+        }
+    }
+
+    // Counts and timings down the left, and the inspector down the right.
+    pub fn draw_panels(
+        &self,
+        world: &World,
+        game_state: &GameState,
+        particles: &ParticleStorage,
+        hud: &Hud,
+        theme: &Theme,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        let worst = self.frame_times.iter().copied().fold(0.0, f32::max);
+        let counts = [
+            ("transform", count::<Transform>(world)),
+            ("ball", count::<Ball>(world)),
+            ("bullet", count::<Bullet>(world)),
+            ("paddle", count::<Bounds>(world)),
+            ("controls", count::<ControlType>(world)),
+            ("health", count::<PaddleHealth>(world)),
+            ("actions", count::<PaddleActions>(world)),
+            ("effects", count::<TimedEffects>(world)),
+            ("power-up", count::<PowerUp>(world)),
+            ("collider", count::<Collider>(world)),
+            ("mover", count::<Mover>(world)),
+            ("sprite", count::<Sprite>(world)),
+            ("brain", count::<Brain>(world)),
+        ];
+        let mut lines = vec![
+            "[F3] debug  [F4] inspector".to_string(),
+            format!(
+                "{:.0} fps  {:.1}ms avg  {:.1}ms worst",
+                1.0 / average.max(f32::EPSILON),
+                average * 1000.0,
+                worst * 1000.0
+            ),
+            format!("{} entities", world.len()),
+            format!("{} / {} particles", particles.alive(), particles.budget()),
+        ];
+        lines.extend(
+            counts
+                .iter()
+                .map(|(name, count)| format!("{:>4} {}", count, name)),
+        );
+        for (i, line) in lines.iter().enumerate() {
+            hud.text(
+                line,
+                Anchor::TopLeft,
+                (32.0, 120.0 + i as f32 * 20.0),
+                18.0,
+                theme.muted,
+            );
+        }
+        // How the last frame compares to a 60fps budget.
+        hud.bar(
+            Anchor::TopLeft,
+            (32.0, 112.0),
+            (200.0, 4.0),
+            self.frame_times.last().copied().unwrap_or(0.0) * 60.0,
+            theme.warning,
+        );
+
+        if !self.inspecting {
+            return;
+        }
+        let mut lines = vec![match self.selected {
+            Some(entity) => format!("Inspecting {:?}  [PgUp/PgDn] pick  [ and ] change", entity),
+            None => "Click something to inspect it  [PgUp/PgDn] pick  [ and ] change".to_string(),
+        }];
+        for (i, field) in self.fields(world).iter().enumerate() {
+            lines.push(format!(
+                "{}{}: {}",
+                if i == self.cursor { "> " } else { "" },
+                field.label(),
+                value(*field, world, self.selected, game_state)
+            ));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            hud.text(
+                line,
+                Anchor::TopRight,
+                (-32.0, 120.0 + i as f32 * 20.0),
+                18.0,
+                if i == self.cursor + 1 {
+                    theme.warning
+                } else {
+                    theme.muted
+                },
+            );
+        }
+    }
+}
+
+fn count<T: Component>(world: &World) -> usize {
+    world.query::<&T>().iter().count()
+}
+
+// Whatever's closest to a spot, if anything's close enough.
+fn pick(world: &World, at: (f32, f32)) -> Option<Entity> {
+    world
+        .query::<&Transform>()
+        .iter()
+        .map(|(id, transform)| {
+            (
+                id,
+                square_distance(at.0, at.1, transform.position.0, transform.position.1),
+            )
+        })
+        .filter(|(_id, distance)| *distance <= PICK_RADIUS.powf(2.0))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _distance)| id)
+}
+
+// Outlining a collision shape.
+pub fn draw_shape(shape: &Shape, position: (f32, f32), color: Color) {
+    let (a, b) = shape.endpoints(position);
+    let radius = shape.radius();
+    if radius > 0.0 {
+        draw_circle_lines(a.0, a.1, radius, 1.0, color);
+        draw_circle_lines(b.0, b.1, radius, 1.0, color);
+    }
+    let length = square_distance(a.0, a.1, b.0, b.1).sqrt();
+    if length == 0.0 {
+        return;
+    }
+    // Running the sides along either side of the core segment.
+    let normal = (-(b.1 - a.1) / length, (b.0 - a.0) / length);
+    for side in [-radius, radius] {
+        draw_line(
+            a.0 + normal.0 * side,
+            a.1 + normal.1 * side,
+            b.0 + normal.0 * side,
+            b.1 + normal.1 * side,
+            1.0,
+            color,
+        );
+        if radius == 0.0 {
+            break;
+        }
+    }
+}
+
+// A field's current value, for showing.
+fn value(field: Field, world: &World, entity: Option<Entity>, game_state: &GameState) -> String {
+    match field {
+        Field::LeftScore => return game_state.left_score.to_string(),
+        Field::RightScore => return game_state.right_score.to_string(),
+        Field::Knockout => return game_state.knockout.label().to_string(),
+        Field::HorizontalMovement => return game_state.horizontal_movement.to_string(),
+        Field::Theme => return THEMES[game_state.theme].name.to_string(),
+        _ => {}
+    }
+    let Some(entity) = entity else {
+        return "-".to_string();
+    };
+    let text = match field {
+        Field::PositionX | Field::PositionY | Field::VelocityX | Field::VelocityY => {
+            world.get::<&Transform>(entity).ok().map(|transform| {
+                let value = match field {
+                    Field::PositionX => transform.position.0,
+                    Field::PositionY => transform.position.1,
+                    Field::VelocityX => transform.velocity.0,
+                    _ => transform.velocity.1,
+                };
+                format!("{:.2}", value)
+            })
+        }
+        Field::HalfWidth => world
+            .get::<&Bounds>(entity)
+            .ok()
+            .map(|bounds| format!("{:.1}", bounds.0)),
+        Field::HalfHeight => world
+            .get::<&Bounds>(entity)
+            .ok()
+            .map(|bounds| format!("{:.1}", bounds.1)),
+        Field::BallRadius | Field::BallSpeed | Field::BallSpin => {
+            world.get::<&Ball>(entity).ok().map(|ball| {
+                let value = match field {
+                    Field::BallRadius => ball.radius,
+                    Field::BallSpeed => ball.speed,
+                    _ => ball.spin,
+                };
+                format!("{:.3}", value)
+            })
+        }
+        Field::RegenRate => world
+            .get::<&PaddleHealth>(entity)
+            .ok()
+            .map(|health| format!("{:.1}", health.regen_rate)),
+        Field::Destroyed => world
+            .get::<&PaddleHealth>(entity)
+            .ok()
+            .map(|health| health.destroyed.to_string()),
+        Field::Tracking => Some(match world.get::<&Brain>(entity) {
+            Ok(brain) => format!("{:.2}", brain.tracking),
+            Err(_) => format!("{:.2} (default)", Brain::default().tracking),
+        }),
+        _ => None,
+    };
+    text.unwrap_or_else(|| "-".to_string())
+}
+
+// Nudging a field along by some number of steps. (Toggles and cycles just flip over.)
+fn adjust(
+    field: Field,
+    world: &mut World,
+    entity: Option<Entity>,
+    game_state: &mut GameState,
+    steps: f32,
+) {
+    let amount = field.step() * steps;
+    match field {
+        Field::LeftScore => game_state.left_score = (game_state.left_score + steps as i32).max(0),
+        Field::RightScore => {
+            game_state.right_score = (game_state.right_score + steps as i32).max(0)
+        }
+        Field::Knockout => game_state.knockout = game_state.knockout.next(),
+        Field::HorizontalMovement => {
+            game_state.horizontal_movement = !game_state.horizontal_movement
+        }
+        Field::Theme => game_state.theme = (game_state.theme + 1) % THEMES.len(),
+        _ => {}
+    }
+    let Some(entity) = entity else {
+        return;
+    };
+    match field {
+        Field::PositionX | Field::PositionY | Field::VelocityX | Field::VelocityY => {
+            if let Ok(mut transform) = world.get::<&mut Transform>(entity) {
+                match field {
+                    Field::PositionX => transform.position.0 += amount,
+                    Field::PositionY => transform.position.1 += amount,
+                    Field::VelocityX => transform.velocity.0 += amount,
+                    _ => transform.velocity.1 += amount,
+                }
+            }
+        }
+        Field::HalfWidth | Field::HalfHeight => {
+            if let Ok(mut bounds) = world.get::<&mut Bounds>(entity) {
+                match field {
+                    Field::HalfWidth => bounds.0 = (bounds.0 + amount).max(1.0),
+                    _ => bounds.1 = (bounds.1 + amount).max(1.0),
+                }
+            }
+        }
+        Field::BallRadius | Field::BallSpeed | Field::BallSpin => {
+            if let Ok(mut ball) = world.get::<&mut Ball>(entity) {
+                match field {
+                    Field::BallRadius => ball.radius = (ball.radius + amount).max(1.0),
+                    Field::BallSpeed => ball.speed = (ball.speed + amount).max(0.1),
+                    _ => ball.spin += amount,
+                }
+            }
+        }
+        Field::RegenRate => {
+            if let Ok(mut health) = world.get::<&mut PaddleHealth>(entity) {
+                health.regen_rate = (health.regen_rate + amount).max(0.0);
+            }
+        }
+        Field::Destroyed => {
+            if let Ok(mut health) = world.get::<&mut PaddleHealth>(entity) {
+                health.destroyed = !health.destroyed;
+            }
+        }
+        Field::Tracking => {
+            let mut brain = world
+                .get::<&Brain>(entity)
+                .map(|brain| *brain)
+                .unwrap_or_default();
+            brain.tracking = (brain.tracking + amount).max(0.0);
+            world.insert_one(entity, brain).unwrap();
+        }
+        _ => {}
+    }
+}
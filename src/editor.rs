@@ -0,0 +1,403 @@
+use std::path::PathBuf;
+
+use macroquad::prelude::*;
+
+use crate::collision::{circle_contact, Shape};
+use crate::level::*;
+use crate::obstacles::{build_obstacle, draw_obstacle, to_screen};
+use crate::theme::Theme;
+
+// The grid things snap to, in pixels, and how far one press of R turns a wall.
+const GRID: f32 = 32.0;
+const ROTATE_STEP: f32 = std::f32::consts::PI / 12.0;
+
+// What a left click does.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Tool {
+    Select,
+    Bumper,
+    Wall,
+    Block,
+    Portal,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 5] = [
+        Tool::Select,
+        Tool::Bumper,
+        Tool::Wall,
+        Tool::Block,
+        Tool::Portal,
+    ];
+    const KEYS: [KeyCode; 5] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Select => "Select",
+            Tool::Bumper => "Bumper",
+            Tool::Wall => "Wall",
+            Tool::Block => "Block",
+            Tool::Portal => "Portal",
+        }
+    }
+
+    // A fresh obstacle of this kind, centered on a spot (in pixels.)
+    fn new_obstacle(&self, at: (f32, f32)) -> Option<ObstacleDef> {
+        let point = |dx: f32, dy: f32| to_level((at.0 + dx, at.1 + dy));
+        match self {
+            Tool::Select => None,
+            Tool::Bumper => Some(ObstacleDef::Bumper {
+                at: point(0.0, 0.0),
+                radius: 32.0,
+            }),
+            Tool::Wall => Some(ObstacleDef::Wall {
+                from: point(0.0, -64.0),
+                to: point(0.0, 64.0),
+                thickness: 12.0,
+            }),
+            Tool::Block => Some(ObstacleDef::Block {
+                from: point(0.0, -48.0),
+                to: point(0.0, 48.0),
+                thickness: 16.0,
+                hits: 3,
+            }),
+            Tool::Portal => Some(ObstacleDef::Portal {
+                at: point(0.0, 0.0),
+                exit: point(0.0, 128.0),
+                radius: 28.0,
+            }),
+        }
+    }
+}
+
+// Whatever's currently picked in the editor.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Selection {
+    Obstacle(usize),
+    PortalExit(usize),
+    Spawn(SpawnKind),
+}
+
+// What the game should do after an editor frame.
+pub enum EditorAction {
+    Stay,
+    Leave,
+    TestPlay,
+}
+
+// Going from pixels to level coordinates (fractions of the screen.)
+fn to_level(point: (f32, f32)) -> (f32, f32) {
+    (point.0 / screen_width(), point.1 / screen_height())
+}
+
+pub struct Editor {
+    pub level: Level,
+    pub path: PathBuf,
+    tool: Tool,
+    selected: Option<Selection>,
+    drag_from: Option<(f32, f32)>, // Where the cursor was last frame while dragging, in pixels.
+    snap: bool,
+    message: Option<(String, f64)>,
+}
+
+impl Editor {
+    pub fn new(level: Level, path: PathBuf) -> Self {
+        Editor {
+            level,
+            path,
+            tool: Tool::Select,
+            selected: None,
+            drag_from: None,
+            snap: true,
+            message: None,
+        }
+    }
+
+    fn notify(&mut self, text: String, current_time: f64) {
+        self.message = Some((text, current_time + 3.0));
+    }
+
+    // The mouse, snapped to the grid if that's turned on.
+    fn cursor(&self) -> (f32, f32) {
+        let (x, y) = mouse_position();
+        if self.snap {
+            ((x / GRID).round() * GRID, (y / GRID).round() * GRID)
+        } else {
+            (x, y)
+        }
+    }
+
+    // Finding whatever's under a spot (in pixels), favouring spawn points and the top-most obstacle.
+    fn pick(&self, at: (f32, f32)) -> Option<Selection> {
+        for kind in SpawnKind::ALL {
+            let spot = self.level.spawn_point(kind);
+            if circle_contact(&Shape::Circle { radius: 16.0 }, spot, at, 4.0).is_some() {
+                return Some(Selection::Spawn(kind));
+            }
+        }
+        for (index, placed) in self.level.obstacles.iter().enumerate().rev() {
+            let (position, shape, _obstacle) = build_obstacle(&placed.def);
+            if circle_contact(&shape, position, at, 6.0).is_some() {
+                return Some(Selection::Obstacle(index));
+            }
+            if let ObstacleDef::Portal { exit, radius, .. } = placed.def {
+                let shape = Shape::Circle {
+                    radius: radius / 2.0,
+                };
+                if circle_contact(&shape, to_screen(exit), at, 6.0).is_some() {
+                    return Some(Selection::PortalExit(index));
+                }
+            }
+        }
+        None
+    }
+
+    fn move_selection(&mut self, delta: (f32, f32)) {
+        let delta = to_level(delta);
+        match self.selected {
+            Some(Selection::Obstacle(index)) => self.level.obstacles[index].def.translate(delta),
+            Some(Selection::PortalExit(index)) => {
+                if let ObstacleDef::Portal { exit, .. } = &mut self.level.obstacles[index].def {
+                    *exit = (exit.0 + delta.0, exit.1 + delta.1);
+                }
+            }
+            Some(Selection::Spawn(kind)) => {
+                let spot = to_level(self.level.spawn_point(kind));
+                self.level.spawns[kind as usize] = Some((spot.0 + delta.0, spot.1 + delta.1));
+            }
+            None => {}
+        }
+    }
+
+    // Spinning walls and blocks around their middle. (Done in pixels so it isn't squashed.)
+    fn rotate_selection(&mut self, angle: f32) {
+        let Some(Selection::Obstacle(index)) = self.selected else {
+            return;
+        };
+        if let ObstacleDef::Wall { from, to, .. } | ObstacleDef::Block { from, to, .. } =
+            &mut self.level.obstacles[index].def
+        {
+            let (a, b) = (to_screen(*from), to_screen(*to));
+            let middle = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+            let (sin, cos) = angle.sin_cos();
+            let turn = |p: (f32, f32)| {
+                let (dx, dy) = (p.0 - middle.0, p.1 - middle.1);
+                to_level((
+                    middle.0 + dx * cos - dy * sin,
+                    middle.1 + dx * sin + dy * cos,
+                ))
+            };
+            *from = turn(a);
+            *to = turn(b);
+        }
+    }
+
+    fn delete_selection(&mut self) {
+        match self.selected.take() {
+            Some(Selection::Obstacle(index)) | Some(Selection::PortalExit(index)) => {
+                self.level.obstacles.remove(index);
+            }
+            Some(Selection::Spawn(kind)) => self.level.spawns[kind as usize] = None,
+            None => {}
+        }
+    }
+
+    pub fn update(&mut self, current_time: f64) -> EditorAction {
+        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Tab) {
+            return EditorAction::Leave;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            return EditorAction::TestPlay;
+        }
+
+        for (tool, key) in Tool::ALL.iter().zip(Tool::KEYS) {
+            if is_key_pressed(key) {
+                self.tool = *tool;
+            }
+        }
+        if is_key_pressed(KeyCode::G) {
+            self.snap = !self.snap;
+        }
+
+        // Saving and loading.
+        let control = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        if control && is_key_pressed(KeyCode::S) {
+            let text = match self.level.save(&self.path) {
+                Ok(()) => format!("Saved to {}", self.path.display()),
+                Err(error) => format!("Couldn't save: {}", error),
+            };
+            self.notify(text, current_time);
+        }
+        if control && is_key_pressed(KeyCode::O) {
+            let text = match Level::load(&self.path) {
+                Ok(level) => {
+                    self.level = level;
+                    self.selected = None;
+                    format!("Loaded {}", self.path.display())
+                }
+                Err(error) => format!("Couldn't load: {}", error),
+            };
+            self.notify(text, current_time);
+        }
+
+        // Tweaking the selection.
+        if is_key_pressed(KeyCode::Delete) || is_key_pressed(KeyCode::Backspace) {
+            self.delete_selection();
+        }
+        let wheel = mouse_wheel().1;
+        if is_key_pressed(KeyCode::R) || wheel > 0.0 {
+            self.rotate_selection(ROTATE_STEP);
+        }
+        if wheel < 0.0 {
+            self.rotate_selection(-ROTATE_STEP);
+        }
+        if let Some(Selection::Obstacle(index)) = self.selected {
+            if is_key_pressed(KeyCode::Equal) {
+                self.level.obstacles[index].def.resize(1.25);
+            }
+            if is_key_pressed(KeyCode::Minus) {
+                self.level.obstacles[index].def.resize(0.8);
+            }
+        }
+
+        // And the mouse.
+        let cursor = self.cursor();
+        if is_mouse_button_pressed(MouseButton::Left) {
+            match self.tool.new_obstacle(cursor) {
+                Some(def) => {
+                    self.level
+                        .obstacles
+                        .push(PlacedObstacle { def, motion: None });
+                    self.selected = Some(Selection::Obstacle(self.level.obstacles.len() - 1));
+                }
+                None => self.selected = self.pick(mouse_position()),
+            }
+            self.drag_from = Some(cursor);
+        }
+        if let Some(from) = self.drag_from {
+            if is_mouse_button_down(MouseButton::Left) {
+                self.move_selection((cursor.0 - from.0, cursor.1 - from.1));
+                self.drag_from = Some(cursor);
+            } else {
+                self.drag_from = None;
+            }
+        }
+        EditorAction::Stay
+    }
+
+    pub fn draw(&self, current_time: f64, theme: &Theme) {
+        clear_background(theme.background);
+
+        // The grid.
+        if self.snap {
+            let color = Color {
+                a: 0.05,
+                ..theme.foreground
+            };
+            let mut x = 0.0;
+            while x < screen_width() {
+                draw_line(x, 0.0, x, screen_height(), 1.0, color);
+                x += GRID;
+            }
+            let mut y = 0.0;
+            while y < screen_height() {
+                draw_line(0.0, y, screen_width(), y, 1.0, color);
+                y += GRID;
+            }
+        }
+
+        draw_goals(&self.level, theme);
+
+        // Everything in the level, with the path of anything that moves.
+        for (index, placed) in self.level.obstacles.iter().enumerate() {
+            let (position, shape, obstacle) = build_obstacle(&placed.def);
+            if let Some(motion) = placed.motion {
+                let offset = to_screen(motion.offset);
+                draw_line(
+                    position.0 - offset.0,
+                    position.1 - offset.1,
+                    position.0 + offset.0,
+                    position.1 + offset.1,
+                    1.0,
+                    theme.dim,
+                );
+            }
+            draw_obstacle(position, &shape, &obstacle, theme);
+            let highlight = match self.selected {
+                Some(Selection::Obstacle(selected)) if selected == index => Some(position),
+                Some(Selection::PortalExit(selected)) if selected == index => match placed.def {
+                    ObstacleDef::Portal { exit, .. } => Some(to_screen(exit)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(spot) = highlight {
+                draw_circle_lines(spot.0, spot.1, 12.0, 2.0, theme.warning);
+            }
+        }
+
+        // Spawn points.
+        for kind in SpawnKind::ALL {
+            let spot = self.level.spawn_point(kind);
+            let color = if self.selected == Some(Selection::Spawn(kind)) {
+                theme.warning
+            } else {
+                theme.foreground
+            };
+            draw_circle_lines(spot.0, spot.1, 16.0, 2.0, color);
+            let letter = &kind.keyword()[0..1].to_uppercase();
+            let size = measure_text(letter, None, 24, 1.0);
+            draw_text(
+                letter,
+                spot.0 - size.width / 2.0,
+                spot.1 + size.height / 2.0,
+                24.0,
+                color,
+            );
+        }
+
+        // The cursor, so it's clear where things will land.
+        let cursor = self.cursor();
+        draw_circle_lines(cursor.0, cursor.1, 4.0, 1.0, theme.foreground);
+
+        // Which tool is in hand, and how to drive this thing.
+        let mut x = 16.0;
+        for (index, tool) in Tool::ALL.iter().enumerate() {
+            let text = format!("[{}] {}", index + 1, tool.label());
+            draw_text(
+                &text,
+                x,
+                32.0,
+                24.0,
+                if *tool == self.tool {
+                    theme.warning
+                } else {
+                    theme.muted
+                },
+            );
+            x += measure_text(&text, None, 24, 1.0).width + 24.0;
+        }
+        draw_text(
+            &format!(
+                "{}  [G] snap {}  [R]/wheel rotate  [+/-] size  [Del] delete  [Ctrl+S] save  [Ctrl+O] load  [Enter] test  [Esc] back",
+                self.path.display(),
+                if self.snap { "on" } else { "off" }
+            ),
+            16.0,
+            screen_height() - 16.0,
+            20.0,
+            theme.muted,
+        );
+        if let Some((text, until)) = &self.message {
+            if current_time < *until {
+                draw_text(text, 16.0, 64.0, 24.0, theme.foreground);
+            }
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+use crate::particles::{Blend, Gradient, Particle, ParticleShape, ParticleStorage};
+
+// Where the emitter presets live, relative to wherever the game is launched from. A copy is built
+// in too, so the game still has its effects if the file goes missing.
+pub const EMITTER_FILE: &str = "emitters.txt";
+const BUILTIN_EMITTERS: &str = include_str!("../emitters.txt");
+
+// Everything the game asks for by name.
+pub const EMITTER_NAMES: [&str; 10] = [
+    "ambient_stars",
+    "paddle_trail",
+    "ball_trail",
+    "impact_spark",
+    "goal_burst",
+    "dash_burst",
+    "parry_flash",
+    "knockout_burst",
+    "power_up_pop",
+    "block_break",
+];
+
+// Game velocities are per physics step, and get carried over at the old sixty-frame pace.
+const FRAME_RATE: f32 = 60.0;
+
+// A recipe for a puff of particles. Built up a piece at a time, then emitted wherever it's needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub count: i32,
+    pub lifetime: (f32, f32), // Seconds, and how much that varies either way.
+    pub size: (f32, f32),
+    pub end_size: f32,        // As a fraction of the starting size.
+    pub spread: (f32, f32),   // How far from the spot particles can start.
+    pub velocity: (f32, f32), // Pixels per second.
+    pub jitter: (f32, f32),   // Random velocity on top of that, either way.
+    pub inherit: f32,         // How much of the emitting thing's velocity carries over.
+    pub gradient: Gradient,
+    pub fade: f32, // The last fraction of a particle's life spent fading out.
+    pub gravity: f32,
+    pub drag: f32,
+    pub blend: Blend,
+    pub shape: ParticleShape,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            count: 1,
+            lifetime: (0.5, 0.0),
+            size: (4.0, 0.0),
+            end_size: 0.0,
+            spread: (0.0, 0.0),
+            velocity: (0.0, 0.0),
+            jitter: (0.0, 0.0),
+            inherit: 0.0,
+            gradient: Gradient::solid(WHITE),
+            fade: 0.0,
+            gravity: 0.0,
+            drag: 0.0,
+            blend: Blend::Alpha,
+            shape: ParticleShape::Dot,
+        }
+    }
+}
+
+impl Emitter {
+    pub fn new() -> Self {
+        Emitter::default()
+    }
+
+    pub fn count(mut self, count: i32) -> Self {
+        self.count = count;
+        self
+    }
+
+    pub fn lifetime(mut self, seconds: f32, variance: f32) -> Self {
+        self.lifetime = (seconds, variance);
+        self
+    }
+
+    pub fn size(mut self, size: f32, variance: f32) -> Self {
+        self.size = (size, variance);
+        self
+    }
+
+    pub fn end_size(mut self, fraction: f32) -> Self {
+        self.end_size = fraction;
+        self
+    }
+
+    pub fn spread(mut self, x: f32, y: f32) -> Self {
+        self.spread = (x, y);
+        self
+    }
+
+    pub fn velocity(mut self, x: f32, y: f32) -> Self {
+        self.velocity = (x, y);
+        self
+    }
+
+    pub fn jitter(mut self, x: f32, y: f32) -> Self {
+        self.jitter = (x, y);
+        self
+    }
+
+    pub fn inherit(mut self, amount: f32) -> Self {
+        self.inherit = amount;
+        self
+    }
+
+    // Starting the colours over with just the one.
+    pub fn color(mut self, color: Color) -> Self {
+        self.gradient = Gradient::solid(color);
+        self
+    }
+
+    // Adding a colour to pass through, `at` a point in the particle's life from 0 to 1.
+    pub fn stop(mut self, at: f32, color: Color) -> Self {
+        self.gradient = self.gradient.stop(at, color);
+        self
+    }
+
+    pub fn tint(mut self, tint: Color) -> Self {
+        self.gradient = self.gradient.tint(tint);
+        self
+    }
+
+    pub fn fade(mut self, fraction: f32) -> Self {
+        self.fade = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn blend(mut self, blend: Blend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn shape(mut self, shape: ParticleShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn gravity(mut self, gravity: f32) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    // Scaling up the size and scatter, for harder hits.
+    pub fn intensity(mut self, amount: f32) -> Self {
+        self.size = (self.size.0 * amount, self.size.1 * amount);
+        self.jitter = (self.jitter.0 * amount, self.jitter.1 * amount);
+        self
+    }
+
+    // Throwing the particles out at a spot, carrying along some of `velocity` (per physics step.)
+    pub fn emit(
+        &self,
+        particles: &mut ParticleStorage,
+        position: (f32, f32),
+        velocity: (f32, f32),
+    ) {
+        let vary = |value: f32, variance: f32| value + rand::gen_range(-variance, variance);
+        // Thinning things out, rounding randomly so single-particle trails still show up sometimes.
+        let scaled = self.count as f32 * particles.density();
+        let count = scaled.floor() as i32 + (rand::gen_range(0.0, 1.0) < scaled.fract()) as i32;
+        for _i in 0..count {
+            let size = vary(self.size.0, self.size.1);
+            let spawned = particles.spawn(Particle {
+                position: (
+                    vary(position.0, self.spread.0),
+                    vary(position.1, self.spread.1),
+                ),
+                velocity: (
+                    vary(self.velocity.0, self.jitter.0) + velocity.0 * self.inherit * FRAME_RATE,
+                    vary(self.velocity.1, self.jitter.1) + velocity.1 * self.inherit * FRAME_RATE,
+                ),
+                size,
+                end_size: size * self.end_size,
+                gradient: self.gradient,
+                fade: self.fade,
+                gravity: self.gravity,
+                drag: self.drag,
+                blend: self.blend,
+                shape: self.shape,
+                age: 0.0,
+                lifetime: vary(self.lifetime.0, self.lifetime.1),
+            });
+            if !spawned {
+                break;
+            }
+        }
+    }
+}
+
+// Reading a preset file. Each preset starts with its name in brackets, followed by one setting a
+// line. Anything left out keeps the default. '#' for comments:
+//
+//   [impact_spark]
+//   count 3
+//   lifetime <seconds> <variance>
+//   size <pixels> <variance>
+//   end_size <fraction>
+//   spread <x> <y>
+//   velocity <x> <y>
+//   jitter <x> <y>
+//   inherit <amount>
+//   color <r> <g> <b> <a>
+//   stop <0 to 1> <r> <g> <b> <a>    (another colour to blend into, later in its life)
+//   fade <fraction of its life>
+//   gravity <pixels per second squared>
+//   drag <fraction per second>
+//   blend <alpha|additive>
+//   shape <dot|square|streak>
+pub fn parse_emitters(source: &str) -> Result<HashMap<String, Emitter>, String> {
+    let mut presets: HashMap<String, Emitter> = HashMap::new();
+    let mut current: Option<String> = None;
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(name) = text
+            .strip_prefix('[')
+            .and_then(|text| text.strip_suffix(']'))
+        {
+            presets.insert(name.trim().to_string(), Emitter::new());
+            current = Some(name.trim().to_string());
+            continue;
+        }
+        let Some(emitter) = current.as_ref().and_then(|name| presets.get_mut(name)) else {
+            return Err(format!("line {}: setting outside of a [preset]", line));
+        };
+
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let (keyword, rest) = (words[0], &words[1..]);
+
+        // The couple of settings that take a word rather than numbers.
+        match (keyword, rest.first().copied()) {
+            ("blend", Some(word)) => {
+                *emitter = emitter.blend(match word {
+                    "alpha" => Blend::Alpha,
+                    "additive" => Blend::Additive,
+                    other => return Err(format!("line {}: unknown blend '{}'", line, other)),
+                });
+                continue;
+            }
+            ("shape", Some(word)) => {
+                *emitter = emitter.shape(match word {
+                    "dot" => ParticleShape::Dot,
+                    "square" => ParticleShape::Square,
+                    "streak" => ParticleShape::Streak,
+                    other => return Err(format!("line {}: unknown shape '{}'", line, other)),
+                });
+                continue;
+            }
+            ("blend", None) => {
+                return Err(format!("line {}: blend needs alpha or additive", line));
+            }
+            ("shape", None) => {
+                return Err(format!("line {}: shape needs dot, square or streak", line));
+            }
+            _ => {}
+        }
+        let n = rest
+            .iter()
+            .map(|word| {
+                word.parse::<f32>()
+                    .map_err(|_| format!("line {}: '{}' isn't a number", line, word))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let wanted = match keyword {
+            "count" | "end_size" | "inherit" | "fade" | "gravity" | "drag" => 1,
+            "lifetime" | "size" | "spread" | "velocity" | "jitter" => 2,
+            "color" => 4,
+            "stop" => 5,
+            other => return Err(format!("line {}: unknown setting '{}'", line, other)),
+        };
+        if n.len() != wanted {
+            return Err(format!(
+                "line {}: {} takes {} numbers, found {}",
+                line,
+                keyword,
+                wanted,
+                n.len()
+            ));
+        }
+        *emitter = match keyword {
+            "count" => emitter.count(n[0] as i32),
+            "lifetime" => emitter.lifetime(n[0], n[1]),
+            "size" => emitter.size(n[0], n[1]),
+            "end_size" => emitter.end_size(n[0]),
+            "spread" => emitter.spread(n[0], n[1]),
+            "velocity" => emitter.velocity(n[0], n[1]),
+            "jitter" => emitter.jitter(n[0], n[1]),
+            "inherit" => emitter.inherit(n[0]),
+            "color" => emitter.color(Color::new(n[0], n[1], n[2], n[3])),
+            "stop" => emitter.stop(n[0], Color::new(n[1], n[2], n[3], n[4])),
+            "fade" => emitter.fade(n[0]),
+            "gravity" => emitter.gravity(n[0]),
+            _ => emitter.drag(n[0]),
+        };
+    }
+    Ok(presets)
+}
+
+// Loading the presets from disk, falling back on the built-in copy if anything goes wrong.
+pub fn load_emitters() -> HashMap<String, Emitter> {
+    let presets = std::fs::read_to_string(EMITTER_FILE)
+        .map_err(|error| format!("{}: {}", EMITTER_FILE, error))
+        .and_then(|source| {
+            parse_emitters(&source).map_err(|error| format!("{}: {}", EMITTER_FILE, error))
+        })
+        .unwrap_or_else(|error| {
+            eprintln!(
+                "Couldn't load emitters, using the built-in ones. ({})",
+                error
+            );
+            parse_emitters(BUILTIN_EMITTERS).unwrap_or_default()
+        });
+    for name in EMITTER_NAMES {
+        if !presets.contains_key(name) {
+            eprintln!("Emitter preset '{}' is missing.", name);
+        }
+    }
+    presets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVERYTHING: &str = "
+# A comment.
+[everything]
+count 12
+lifetime 0.8 0.2   # Trailing comments too.
+size 6 1
+end_size 0.5
+spread 4 2
+velocity 0 -30
+jitter 40 20
+inherit 0.25
+color 1 0.5 0 1
+stop 0.5 0 0 1 0.5
+fade 0.3
+gravity 90
+drag 1.5
+blend additive
+shape streak
+
+[bare]
+";
+
+    fn parse_error(source: &str) -> String {
+        parse_emitters(source).unwrap_err()
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let presets = parse_emitters(EVERYTHING).unwrap();
+        assert_eq!(
+            presets["everything"],
+            Emitter::new()
+                .count(12)
+                .lifetime(0.8, 0.2)
+                .size(6.0, 1.0)
+                .end_size(0.5)
+                .spread(4.0, 2.0)
+                .velocity(0.0, -30.0)
+                .jitter(40.0, 20.0)
+                .inherit(0.25)
+                .color(Color::new(1.0, 0.5, 0.0, 1.0))
+                .stop(0.5, Color::new(0.0, 0.0, 1.0, 0.5))
+                .fade(0.3)
+                .gravity(90.0)
+                .drag(1.5)
+                .blend(Blend::Additive)
+                .shape(ParticleShape::Streak)
+        );
+        // Anything left out keeps the default.
+        assert_eq!(presets["bare"], Emitter::default());
+        assert_eq!(presets.len(), 2);
+    }
+
+    #[test]
+    fn built_in_emitters_parse() {
+        let presets = parse_emitters(BUILTIN_EMITTERS).unwrap();
+        for name in EMITTER_NAMES {
+            assert!(presets.contains_key(name), "{} is missing", name);
+        }
+    }
+
+    #[test]
+    fn settings_need_a_preset() {
+        assert_eq!(
+            parse_error("count 3\n[late]"),
+            "line 1: setting outside of a [preset]"
+        );
+    }
+
+    #[test]
+    fn unknown_settings() {
+        assert_eq!(
+            parse_error("[a]\nsparkle 3"),
+            "line 2: unknown setting 'sparkle'"
+        );
+    }
+
+    #[test]
+    fn wrong_numbers() {
+        assert_eq!(
+            parse_error("[a]\ncount lots"),
+            "line 2: 'lots' isn't a number"
+        );
+        assert_eq!(
+            parse_error("[a]\n\nlifetime 0.5"),
+            "line 3: lifetime takes 2 numbers, found 1"
+        );
+        assert_eq!(
+            parse_error("[a]\ncolor 1 1 1"),
+            "line 2: color takes 4 numbers, found 3"
+        );
+        assert_eq!(
+            parse_error("[a]\ngravity"),
+            "line 2: gravity takes 1 numbers, found 0"
+        );
+    }
+
+    #[test]
+    fn word_settings() {
+        assert_eq!(
+            parse_error("[a]\nblend"),
+            "line 2: blend needs alpha or additive"
+        );
+        assert_eq!(
+            parse_error("[a]\nshape"),
+            "line 2: shape needs dot, square or streak"
+        );
+        assert_eq!(
+            parse_error("[a]\nblend glow"),
+            "line 2: unknown blend 'glow'"
+        );
+        assert_eq!(
+            parse_error("[a]\nshape star"),
+            "line 2: unknown shape 'star'"
+        );
+    }
+}
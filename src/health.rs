@@ -0,0 +1,146 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::theme::Theme;
+use crate::Bounds;
+
+// The size a fresh paddle starts out at.
+pub const PADDLE_RADIUS: f32 = 16.0;
+pub const PADDLE_LENGTH: f32 = 64.0;
+
+// What happens when a paddle gets shot down to nothing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum KnockoutRule {
+    #[default]
+    Off, // Paddles just bottom out at their minimum size.
+    Point, // A knocked out paddle forfeits the point.
+    Match, // A knocked out paddle forfeits the whole match.
+}
+
+impl KnockoutRule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KnockoutRule::Off => "KO off",
+            KnockoutRule::Point => "KO point",
+            KnockoutRule::Match => "KO match",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            KnockoutRule::Off => KnockoutRule::Point,
+            KnockoutRule::Point => KnockoutRule::Match,
+            KnockoutRule::Match => KnockoutRule::Off,
+        }
+    }
+}
+
+// How beaten up a paddle looks.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DamageState {
+    Healthy,
+    Damaged,
+    Critical,
+    Destroyed,
+}
+
+// A component tracking how much punishment a paddle can still take.
+#[derive(Clone, Copy)]
+pub struct PaddleHealth {
+    pub max_length: f32,
+    pub min_length: f32,
+    pub regen_delay: f64, // Seconds after a hit before the paddle starts growing back.
+    pub regen_rate: f32,  // Length regained per second.
+    pub last_damaged: f64,
+    pub destroyed: bool,
+    pub invulnerable: bool, // For testing, mostly.
+}
+
+impl Default for PaddleHealth {
+    fn default() -> Self {
+        PaddleHealth {
+            max_length: PADDLE_LENGTH,
+            min_length: 16.0,
+            regen_delay: 2.0,
+            regen_rate: 4.0,
+            last_damaged: 0.0,
+            destroyed: false,
+            invulnerable: false,
+        }
+    }
+}
+
+impl PaddleHealth {
+    // Knocking some length off. Returns true if this hit is the one that broke it.
+    pub fn damage(
+        &mut self,
+        bounds: &mut Bounds,
+        amount: f32,
+        current_time: f64,
+        rule: KnockoutRule,
+    ) -> bool {
+        if self.destroyed || self.invulnerable {
+            return false;
+        }
+        self.last_damaged = current_time;
+        bounds.1 = (bounds.1 - amount).max(self.min_length);
+        if bounds.1 <= self.min_length && rule != KnockoutRule::Off {
+            self.destroyed = true;
+            return true;
+        }
+        false
+    }
+
+    // Slowly growing back once it's been left alone for a bit.
+    pub fn regenerate(&self, bounds: &mut Bounds, delta: f32, current_time: f64) {
+        if self.destroyed || current_time - self.last_damaged < self.regen_delay {
+            return;
+        }
+        if bounds.1 < self.max_length {
+            bounds.1 = (bounds.1 + self.regen_rate * delta).min(self.max_length);
+        }
+    }
+
+    // Good as new, for the start of a round.
+    pub fn restore(&mut self, bounds: &mut Bounds) {
+        self.destroyed = false;
+        self.max_length = PADDLE_LENGTH;
+        bounds.0 = PADDLE_RADIUS;
+        bounds.1 = PADDLE_LENGTH;
+    }
+
+    pub fn state(&self, bounds: &Bounds) -> DamageState {
+        let health = (bounds.1 - self.min_length) / (self.max_length - self.min_length);
+        if self.destroyed {
+            DamageState::Destroyed
+        } else if health > 0.66 {
+            DamageState::Healthy
+        } else if health > 0.33 {
+            DamageState::Damaged
+        } else {
+            DamageState::Critical
+        }
+    }
+}
+
+pub fn regenerate_paddles(world: &mut World, delta: f32, current_time: f64) {
+    for (_id, (health, bounds)) in world.query_mut::<(&PaddleHealth, &mut Bounds)>() {
+        health.regenerate(bounds, delta, current_time);
+    }
+}
+
+// The outline colour for each state. (Critical paddles flash, unless flashing's turned off.)
+pub fn damage_color(state: DamageState, current_time: f64, theme: &Theme, flashing: bool) -> Color {
+    match state {
+        DamageState::Healthy => theme.foreground,
+        DamageState::Damaged => theme.warning,
+        DamageState::Critical => {
+            if !flashing || ((current_time * 4.0) % 1.0) < 0.5 {
+                theme.danger
+            } else {
+                theme.foreground
+            }
+        }
+        DamageState::Destroyed => theme.dim,
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::theme::Theme;
+
+// Where a custom HUD font goes. Without one the game sticks to macroquad's built-in font.
+pub const HUD_FONT_FILE: &str = "fonts/hud.ttf";
+
+// The HUD is laid out for a screen this tall, and scaled up or down from there.
+const REFERENCE_HEIGHT: f32 = 720.0;
+
+// The intensity where every part of the music has come in, for filling up the meter.
+const FULL_INTENSITY: f32 = 22.0;
+
+// Which part of the screen a widget hangs off.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Center,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // How far across and down the screen the anchor is, from 0 to 1. The same point of the widget
+    // lines up with it, so right-anchored things grow leftwards and so on.
+    fn fractions(&self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+pub struct Hud {
+    font: Option<Font>,
+    scale: f32, // From the screen size.
+    dpi: f32,   // From the display, so text gets rasterised sharp.
+}
+
+impl Hud {
+    // Loading the HUD font, falling back on the built-in one if it's missing or broken.
+    pub async fn load(path: &str) -> Hud {
+        let font = if Path::new(path).exists() {
+            load_ttf_font(path)
+                .await
+                .map_err(|error| eprintln!("Couldn't load font, using the default. ({})", error))
+                .ok()
+        } else {
+            None
+        };
+        Hud {
+            font,
+            scale: 1.0,
+            dpi: 1.0,
+        }
+    }
+
+    // Catching up with the window size, once a frame.
+    pub fn update(&mut self) {
+        self.scale = (screen_height() / REFERENCE_HEIGHT).max(0.5);
+        self.dpi = screen_dpi_scale().max(1.0);
+    }
+
+    // Text parameters for `size` on-screen pixels, rasterised at the display's resolution.
+    fn params(&self, size: f32, color: Color) -> TextParams<'_> {
+        TextParams {
+            font: self.font.as_ref(),
+            font_size: (size * self.dpi).round() as u16,
+            font_scale: 1.0 / self.dpi,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn measure(&self, text: &str, size: f32) -> TextDimensions {
+        measure_text(
+            text,
+            self.font.as_ref(),
+            (size * self.scale * self.dpi).round() as u16,
+            1.0 / self.dpi,
+        )
+    }
+
+    // The top left corner of a widget, given its anchor, an offset (in unscaled pixels) and its
+    // size (already scaled.)
+    fn place(&self, anchor: Anchor, offset: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        let (across, down) = anchor.fractions();
+        (
+            screen_width() * across + offset.0 * self.scale - size.0 * across,
+            screen_height() * down + offset.1 * self.scale - size.1 * down,
+        )
+    }
+
+    pub fn text(&self, text: &str, anchor: Anchor, offset: (f32, f32), size: f32, color: Color) {
+        let dimensions = self.measure(text, size);
+        let (x, y) = self.place(anchor, offset, (dimensions.width, dimensions.height));
+        draw_text_ex(
+            text,
+            x,
+            y + dimensions.offset_y,
+            self.params(size * self.scale, color),
+        );
+    }
+
+    // Text stuck to something in the arena, starting at `position` along its baseline. It's sized
+    // with the arena rather than the screen, so it isn't scaled.
+    pub fn label(&self, text: &str, position: (f32, f32), size: f32, color: Color) {
+        draw_text_ex(text, position.0, position.1, self.params(size, color));
+    }
+
+    // A bar filled up to `fill`, from 0 to 1, over a faint track.
+    pub fn bar(
+        &self,
+        anchor: Anchor,
+        offset: (f32, f32),
+        size: (f32, f32),
+        fill: f32,
+        color: Color,
+    ) {
+        let size = (size.0 * self.scale, size.1 * self.scale);
+        let (x, y) = self.place(anchor, offset, size);
+        draw_rectangle(x, y, size.0, size.1, Color { a: 0.2, ..color });
+        draw_rectangle(x, y, size.0 * fill.clamp(0.0, 1.0), size.1, color);
+    }
+
+    // A band right across the screen, `height` tall, for putting other widgets on.
+    pub fn strip(&self, top: f32, height: f32, color: Color) {
+        draw_rectangle(
+            0.0,
+            top * self.scale,
+            screen_width(),
+            height * self.scale,
+            color,
+        );
+    }
+
+    // The big line of text along the top, for what's going on.
+    pub fn banner(&self, text: &str, theme: &Theme) {
+        self.text(text, Anchor::Top, (0.0, 40.0), 32.0, theme.foreground);
+    }
+
+    // Lines of settings under the banner.
+    pub fn options(&self, lines: &[String], theme: &Theme) {
+        for (i, line) in lines.iter().enumerate() {
+            self.text(
+                line,
+                Anchor::Top,
+                (0.0, 80.0 + i as f32 * 24.0),
+                20.0,
+                theme.muted,
+            );
+        }
+    }
+
+    pub fn score(&self, left: i32, right: i32, theme: &Theme) {
+        let text = format!("{} - {}", left, right);
+        self.text(&text, Anchor::Bottom, (0.0, -52.0), 32.0, theme.foreground);
+    }
+
+    // How intense things are, as a number and a meter filling up with the music.
+    pub fn intensity(&self, intensity: f32, theme: &Theme) {
+        let text = format!("{}", intensity.round().abs());
+        self.text(&text, Anchor::Bottom, (0.0, -24.0), 24.0, theme.foreground);
+        self.bar(
+            Anchor::Bottom,
+            (0.0, -12.0),
+            (200.0, 4.0),
+            intensity / FULL_INTENSITY,
+            theme.foreground,
+        );
+    }
+}
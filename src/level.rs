@@ -0,0 +1,556 @@
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+use crate::arena::{arena_height, arena_width};
+use crate::theme::Theme;
+
+// Where the arenas live, relative to wherever the game is launched from.
+pub const LEVEL_DIRECTORY: &str = "levels";
+const LEVEL_EXTENSION: &str = "lvl";
+
+// The thinnest a wall (or block) can be shrunk down to in the editor.
+const MIN_THICKNESS: f32 = 2.0;
+
+// Everything that can be placed in an arena. Positions are fractions of the screen,
+// sizes are in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObstacleDef {
+    Bumper {
+        at: (f32, f32),
+        radius: f32,
+    },
+    Wall {
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+    },
+    Block {
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+        hits: i32,
+    },
+    Portal {
+        at: (f32, f32),
+        exit: (f32, f32),
+        radius: f32,
+    },
+}
+
+// Obstacles can optionally swing back and forth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    pub offset: (f32, f32), // How far it travels, again as a fraction of the screen.
+    pub period: f32,        // Seconds for a full there-and-back.
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedObstacle {
+    pub def: ObstacleDef,
+    pub motion: Option<Motion>,
+}
+
+// The spots things start from.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SpawnKind {
+    Left,
+    Right,
+    Ball,
+}
+
+impl SpawnKind {
+    pub const ALL: [SpawnKind; 3] = [SpawnKind::Left, SpawnKind::Right, SpawnKind::Ball];
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SpawnKind::Left => "left",
+            SpawnKind::Right => "right",
+            SpawnKind::Ball => "ball",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub goal_width: f32, // How much of each end is goal, from 0 to 1.
+    pub obstacles: Vec<PlacedObstacle>,
+    pub spawns: [Option<(f32, f32)>; 3], // Indexed by SpawnKind, None for the usual spot.
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level {
+            name: "Classic".to_string(),
+            goal_width: 1.0,
+            obstacles: Vec::new(),
+            spawns: [None; 3],
+        }
+    }
+}
+
+impl ObstacleDef {
+    // Every point that makes up the obstacle, for moving things around.
+    pub fn points_mut(&mut self) -> Vec<&mut (f32, f32)> {
+        match self {
+            ObstacleDef::Bumper { at, .. } => vec![at],
+            ObstacleDef::Wall { from, to, .. } | ObstacleDef::Block { from, to, .. } => {
+                vec![from, to]
+            }
+            ObstacleDef::Portal { at, exit, .. } => vec![at, exit],
+        }
+    }
+
+    pub fn translate(&mut self, delta: (f32, f32)) {
+        for point in self.points_mut() {
+            *point = (point.0 + delta.0, point.1 + delta.1);
+        }
+    }
+
+    // Growing or shrinking whatever counts as the obstacle's size.
+    pub fn resize(&mut self, factor: f32) {
+        match self {
+            ObstacleDef::Bumper { radius, .. } | ObstacleDef::Portal { radius, .. } => {
+                *radius = (*radius * factor).clamp(4.0, 256.0)
+            }
+            // (Never all the way to nothing, or there'd be nothing left to grow back.)
+            ObstacleDef::Wall { thickness, .. } | ObstacleDef::Block { thickness, .. } => {
+                *thickness = (*thickness * factor).clamp(MIN_THICKNESS, 128.0)
+            }
+        }
+    }
+}
+
+// Numbers for the level file, without a pile of trailing zeroes.
+fn format_number(number: f32) -> String {
+    let text = format!("{:.4}", number);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text.is_empty() || text == "-" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+// Pulling `count` numbers off the front of a line.
+fn numbers(words: &[&str], count: usize, line: usize) -> Result<Vec<f32>, String> {
+    if words.len() < count {
+        return Err(format!(
+            "line {}: expected {} numbers, found {}",
+            line,
+            count,
+            words.len()
+        ));
+    }
+    words[0..count]
+        .iter()
+        .map(|word| {
+            word.parse::<f32>()
+                .map_err(|_| format!("line {}: '{}' isn't a number", line, word))
+        })
+        .collect()
+}
+
+impl Level {
+    // Reading the plain text format. One thing per line, '#' for comments:
+    //
+    //   name <text>
+    //   goal <fraction of the screen height>
+    //   bumper <x> <y> <radius>
+    //   wall <x1> <y1> <x2> <y2> <thickness>
+    //   block <x1> <y1> <x2> <y2> <thickness> <hits>
+    //   portal <x> <y> <exit x> <exit y> <radius>
+    //   spawn <left|right|ball> <x> <y>
+    //
+    // Any obstacle can end with `move <dx> <dy> <period>` to make it slide back and forth.
+    pub fn parse(source: &str) -> Result<Level, String> {
+        let mut level = Level::default();
+        for (index, raw) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = raw.split('#').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+            let words = text.split_whitespace().collect::<Vec<_>>();
+            let (keyword, rest) = (words[0], &words[1..]);
+
+            // Splitting off the movement, if there is any. It only ever comes after an obstacle's
+            // own numbers, so a level called "Make your move" is fine.
+            let fixed = match keyword {
+                "bumper" => Some(3),
+                "wall" | "portal" => Some(5),
+                "block" => Some(6),
+                _ => None,
+            };
+            let split = fixed.and_then(|fixed| {
+                rest.iter()
+                    .skip(fixed)
+                    .position(|&word| word == "move")
+                    .map(|position| fixed + position)
+            });
+            let (rest, motion) = match split {
+                Some(split) => {
+                    let n = numbers(&rest[split + 1..], 3, line)?;
+                    (
+                        &rest[..split],
+                        Some(Motion {
+                            offset: (n[0], n[1]),
+                            period: n[2].max(0.1),
+                        }),
+                    )
+                }
+                None => (rest, None),
+            };
+
+            // Only obstacles get to move. (Names are just text.)
+            if matches!(keyword, "goal" | "spawn") && rest.contains(&"move") {
+                return Err(format!("line {}: {} can't move", line, keyword));
+            }
+
+            let def = match keyword {
+                "name" => {
+                    level.name = rest.join(" ");
+                    continue;
+                }
+                "goal" => {
+                    level.goal_width = numbers(rest, 1, line)?[0].clamp(0.0, 1.0);
+                    continue;
+                }
+                "spawn" => {
+                    let kind = SpawnKind::ALL
+                        .iter()
+                        .position(|kind| rest.first() == Some(&kind.keyword()))
+                        .ok_or(format!("line {}: spawn needs left, right or ball", line))?;
+                    let n = numbers(&rest[1..], 2, line)?;
+                    level.spawns[kind] = Some((n[0], n[1]));
+                    continue;
+                }
+                "bumper" => {
+                    let n = numbers(rest, 3, line)?;
+                    ObstacleDef::Bumper {
+                        at: (n[0], n[1]),
+                        radius: n[2],
+                    }
+                }
+                "wall" => {
+                    let n = numbers(rest, 5, line)?;
+                    ObstacleDef::Wall {
+                        from: (n[0], n[1]),
+                        to: (n[2], n[3]),
+                        thickness: n[4],
+                    }
+                }
+                "block" => {
+                    let n = numbers(rest, 6, line)?;
+                    ObstacleDef::Block {
+                        from: (n[0], n[1]),
+                        to: (n[2], n[3]),
+                        thickness: n[4],
+                        hits: n[5] as i32,
+                    }
+                }
+                "portal" => {
+                    let n = numbers(rest, 5, line)?;
+                    ObstacleDef::Portal {
+                        at: (n[0], n[1]),
+                        exit: (n[2], n[3]),
+                        radius: n[4],
+                    }
+                }
+                other => return Err(format!("line {}: unknown keyword '{}'", line, other)),
+            };
+            level.obstacles.push(PlacedObstacle { def, motion });
+        }
+        Ok(level)
+    }
+
+    // Writing the level back out in the same format `parse` reads.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "name {}\ngoal {}\n",
+            self.name,
+            format_number(self.goal_width)
+        );
+        for kind in SpawnKind::ALL {
+            if let Some(spot) = self.spawns[kind as usize] {
+                text += &format!(
+                    "spawn {} {} {}\n",
+                    kind.keyword(),
+                    format_number(spot.0),
+                    format_number(spot.1)
+                );
+            }
+        }
+        for placed in &self.obstacles {
+            let (keyword, numbers) = match placed.def {
+                ObstacleDef::Bumper { at, radius } => ("bumper", vec![at.0, at.1, radius]),
+                ObstacleDef::Wall {
+                    from,
+                    to,
+                    thickness,
+                } => ("wall", vec![from.0, from.1, to.0, to.1, thickness]),
+                ObstacleDef::Block {
+                    from,
+                    to,
+                    thickness,
+                    hits,
+                } => (
+                    "block",
+                    vec![from.0, from.1, to.0, to.1, thickness, hits as f32],
+                ),
+                ObstacleDef::Portal { at, exit, radius } => {
+                    ("portal", vec![at.0, at.1, exit.0, exit.1, radius])
+                }
+            };
+            text += keyword;
+            for number in numbers {
+                text += " ";
+                text += &format_number(number);
+            }
+            if let Some(motion) = placed.motion {
+                text += &format!(
+                    " move {} {} {}",
+                    format_number(motion.offset.0),
+                    format_number(motion.offset.1),
+                    format_number(motion.period)
+                );
+            }
+            text += "\n";
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| format!("{}: {}", parent.display(), error))?;
+        }
+        std::fs::write(path, self.to_text())
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // Where something starts, in pixels.
+    pub fn spawn_point(&self, kind: SpawnKind) -> (f32, f32) {
+        match self.spawns[kind as usize] {
+            Some(spot) => (spot.0 * arena_width(), spot.1 * arena_height()),
+            None => match kind {
+                SpawnKind::Left => (64.0, arena_height() / 2.0),
+                SpawnKind::Right => (arena_width() - 64.0, arena_height() / 2.0),
+                SpawnKind::Ball => (arena_width() / 2.0, arena_height() / 2.0),
+            },
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Level, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        Level::parse(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // Loading a level, falling back on the empty arena if anything goes wrong.
+    pub fn load_or_default(path: &Path) -> Level {
+        Level::load(path).unwrap_or_else(|error| {
+            eprintln!("Couldn't load level, using the classic arena. ({})", error);
+            Level::default()
+        })
+    }
+
+    // Whether a spot along the end of the arena counts as goal.
+    pub fn in_goal(&self, y: f32) -> bool {
+        let half = self.goal_width * arena_height() / 2.0;
+        (y - arena_height() / 2.0).abs() <= half
+    }
+}
+
+// Every level file we can find, in a stable order.
+pub fn available_levels() -> Vec<PathBuf> {
+    let mut paths = std::fs::read_dir(LEVEL_DIRECTORY)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == LEVEL_EXTENSION))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+// Drawing the goal posts, wherever the goal mouth stops.
+pub fn draw_goals(level: &Level, theme: &Theme) {
+    if level.goal_width >= 1.0 {
+        return;
+    }
+    let half = level.goal_width * screen_height() / 2.0;
+    for x in [0.0, screen_width()] {
+        draw_line(
+            x,
+            0.0,
+            x,
+            screen_height() / 2.0 - half,
+            12.0,
+            theme.foreground,
+        );
+        draw_line(
+            x,
+            screen_height() / 2.0 + half,
+            x,
+            screen_height(),
+            12.0,
+            theme.foreground,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLED: [&str; 4] = [
+        include_str!("../levels/01-classic.lvl"),
+        include_str!("../levels/02-bumpers.lvl"),
+        include_str!("../levels/03-fortress.lvl"),
+        include_str!("../levels/04-portals.lvl"),
+    ];
+
+    // Something with one of everything in it.
+    const EVERYTHING: &str = "# A comment, then a bit of everything.
+name Test Arena
+goal 0.75
+spawn left 0.1 0.5
+spawn ball 0.5 0.25
+bumper 0.5 0.5 40 move 0 0.25 6
+wall 0 0.15 0.1 0 12
+block 0.2 0.3 0.2 0.45 16 4   # Trailing comment.
+portal 0.5 0.15 0.5 0.85 28 move -0.1 0 2.5
+";
+
+    fn round_trip(source: &str) {
+        let level = Level::parse(source).unwrap();
+        let reparsed = Level::parse(&level.to_text()).unwrap();
+        assert_eq!(level, reparsed, "{}", level.to_text());
+    }
+
+    #[test]
+    fn parses_everything() {
+        let level = Level::parse(EVERYTHING).unwrap();
+        assert_eq!(level.name, "Test Arena");
+        assert_eq!(level.goal_width, 0.75);
+        assert_eq!(level.spawns[SpawnKind::Left as usize], Some((0.1, 0.5)));
+        assert_eq!(level.spawns[SpawnKind::Right as usize], None);
+        assert_eq!(level.spawns[SpawnKind::Ball as usize], Some((0.5, 0.25)));
+        assert_eq!(level.obstacles.len(), 4);
+        assert_eq!(
+            level.obstacles[0],
+            PlacedObstacle {
+                def: ObstacleDef::Bumper {
+                    at: (0.5, 0.5),
+                    radius: 40.0,
+                },
+                motion: Some(Motion {
+                    offset: (0.0, 0.25),
+                    period: 6.0,
+                }),
+            }
+        );
+        assert_eq!(
+            level.obstacles[2].def,
+            ObstacleDef::Block {
+                from: (0.2, 0.3),
+                to: (0.2, 0.45),
+                thickness: 16.0,
+                hits: 4,
+            }
+        );
+        assert_eq!(level.obstacles[2].motion, None);
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        round_trip(EVERYTHING);
+        round_trip("");
+        for source in BUNDLED {
+            round_trip(source);
+        }
+    }
+
+    #[test]
+    fn names_can_say_move() {
+        round_trip("name Make your move\nbumper 0.5 0.5 40 move 0 0.1 4\n");
+        round_trip("name move\n");
+        let level = Level::parse("name Move it, move it").unwrap();
+        assert_eq!(level.name, "Move it, move it");
+    }
+
+    #[test]
+    fn shrunk_walls_grow_back() {
+        let mut wall = ObstacleDef::Wall {
+            from: (0.1, 0.1),
+            to: (0.9, 0.1),
+            thickness: 12.0,
+        };
+        for _ in 0..100 {
+            wall.resize(0.8);
+        }
+        let thickness = |def: &ObstacleDef| match def {
+            ObstacleDef::Wall { thickness, .. } => *thickness,
+            _ => unreachable!(),
+        };
+        assert_eq!(thickness(&wall), MIN_THICKNESS);
+        wall.resize(1.25);
+        assert!(thickness(&wall) > MIN_THICKNESS);
+        // Even one that started out at nothing.
+        let mut wall = Level::parse("wall 0.1 0.1 0.9 0.1 0").unwrap().obstacles[0].def;
+        wall.resize(1.25);
+        assert_eq!(thickness(&wall), MIN_THICKNESS);
+    }
+
+    #[test]
+    fn bundled_levels_parse() {
+        for source in BUNDLED {
+            let level = Level::parse(source).unwrap();
+            assert!(!level.name.is_empty());
+        }
+    }
+
+    #[test]
+    fn unknown_keyword() {
+        assert_eq!(
+            Level::parse("name Oops\n\nteleporter 0.5 0.5 10"),
+            Err("line 3: unknown keyword 'teleporter'".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_number() {
+        assert_eq!(
+            Level::parse("bumper 0.5 half 40"),
+            Err("line 1: 'half' isn't a number".to_string())
+        );
+        assert_eq!(
+            Level::parse("# Fine so far.\nwall 0 0 1 1"),
+            Err("line 2: expected 5 numbers, found 4".to_string())
+        );
+        assert_eq!(
+            Level::parse("bumper 0.5 0.5 40 move 0 x 6"),
+            Err("line 1: 'x' isn't a number".to_string())
+        );
+    }
+
+    #[test]
+    fn settings_without_anything_to_set() {
+        assert_eq!(
+            Level::parse("move 0 0.25 6"),
+            Err("line 1: unknown keyword 'move'".to_string())
+        );
+        assert_eq!(
+            Level::parse("name Test\ngoal 0.5 move 0 0.25 6"),
+            Err("line 2: goal can't move".to_string())
+        );
+        assert_eq!(
+            Level::parse("spawn middle 0.5 0.5"),
+            Err("line 1: spawn needs left, right or ball".to_string())
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+pub mod achievements;
+pub mod actions;
+pub mod arena;
+pub mod brain;
+pub mod camera;
+pub mod cli;
+pub mod clock;
+pub mod collision;
+pub mod console;
+pub mod debug;
+pub mod editor;
+pub mod emitters;
+pub mod health;
+pub mod hud;
+pub mod level;
+pub mod modes;
+pub mod obstacles;
+pub mod particles;
+pub mod physics;
+pub mod postfx;
+pub mod powerups;
+pub mod profile;
+pub mod spin;
+pub mod sprites;
+pub mod stats;
+pub mod survival;
+pub mod theme;
+pub use achievements::*;
+pub use actions::*;
+pub use arena::*;
+pub use brain::*;
+pub use camera::*;
+pub use cli::*;
+pub use clock::*;
+pub use collision::*;
+pub use console::*;
+pub use debug::*;
+pub use editor::*;
+pub use emitters::*;
+pub use health::*;
+pub use hud::*;
+pub use level::*;
+pub use modes::*;
+pub use obstacles::*;
+pub use particles::*;
+pub use physics::*;
+pub use postfx::*;
+pub use powerups::*;
+pub use profile::*;
+pub use spin::*;
+pub use sprites::*;
+pub use stats::*;
+pub use survival::*;
+pub use theme::*;
+
+// Tracking the phases of a game.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum Phase {
+    #[default]
+    Start,
+    Ongoing,
+    LeftWin,
+    RightWin,
+}
+
+// A component to store an objects position and velocity.
+#[derive(Default, Clone, Copy)]
+pub struct Transform {
+    pub position: (f32, f32),
+    pub velocity: (f32, f32),
+}
+
+// A component to store an object's bounds: half its width and half its height.
+#[derive(Default, Clone, Copy)]
+pub struct Bounds(pub f32, pub f32);
+
+impl Bounds {
+    // What it collides as: the capsule filling the same box, with its round ends tucked inside the
+    // top and bottom. `draw_paddle` draws this same capsule, but sprites still fill the whole box,
+    // so paddle art wants rounded ends to match.
+    pub fn shape(&self) -> Shape {
+        Shape::Capsule {
+            half: (0.0, (self.1 - self.0).max(0.0)),
+            radius: self.0,
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Controls {
+    pub up: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub strafe_left: Vec<KeyCode>,
+    pub strafe_right: Vec<KeyCode>,
+    pub dash: Vec<KeyCode>,
+    pub parry: Vec<KeyCode>,
+}
+
+// For tracking the controls of a given entity. (Also bullet cooldowns)
+pub enum ControlType {
+    AI(f64),
+    Player(Controls, f64),
+}
+
+// The ball!
+#[derive(Default, Clone, Copy)]
+pub struct Ball {
+    pub radius: f32,
+    pub speed: f32,
+    pub last_hit: Option<Entity>, // Whichever paddle touched it last.
+    pub carrying: Option<PowerUpKind>, // A power-up on its way to the next paddle.
+    pub spin: f32,                // Angular velocity, in radians per step.
+    pub rally: u32,               // Paddle hits since it was served.
+    pub angle: f32,               // Only really here for drawing.
+}
+
+// Marks where new balls get served from.
+#[derive(Default, Clone, Copy)]
+pub struct BallSpawn;
+
+#[derive(Default, Clone, Copy)]
+pub struct Bullet {
+    pub radius: f32,
+    pub owner: Option<Entity>,
+}
+
+// Rounds it takes to win a match.
+pub const POINTS_TO_WIN: i32 = 5;
+
+// The game state as a whole.
+#[derive(Default, Clone, Copy)]
+pub struct GameState {
+    pub phase: Phase,
+    pub left_score: i32,
+    pub right_score: i32,
+    pub left_rounds: i32, // Rounds won so far this match. (Multiball rounds take a few goals.)
+    pub right_rounds: i32,
+    pub points_to_win: i32,
+    pub intensity: f32,
+    pub target_color: Color,
+    pub current_color: Color,
+    pub next_power_up: f64,
+    pub mode: GameMode,
+    pub rules: MultiballRules,
+    pub round: RoundState,
+    pub knockout: KnockoutRule,
+    pub knocked_out: bool, // Whether the last round ended in a knockout.
+    pub horizontal_movement: bool,
+    pub theme: usize,         // Which of the THEMES we're using.
+    pub reduced_motion: bool, // Less shaking, flashing and particles.
+    pub time_scale: f32,      // How fast everything moves, 1.0 being normal.
+}
+
+// Creating a constructor for it.
+impl GameState {
+    pub fn new() -> Self {
+        GameState {
+            phase: Phase::Start,
+            left_score: 0,
+            right_score: 0,
+            left_rounds: 0,
+            right_rounds: 0,
+            points_to_win: POINTS_TO_WIN,
+            intensity: 0.0,
+            target_color: BLACK,
+            current_color: BLACK,
+            next_power_up: 0.0,
+            mode: GameMode::Classic,
+            rules: MultiballRules::default(),
+            round: RoundState::default(),
+            knockout: KnockoutRule::Off,
+            knocked_out: false,
+            horizontal_movement: false,
+            theme: 0,
+            reduced_motion: false,
+            time_scale: 1.0,
+        }
+    }
+
+    // Back to nothing all, for a fresh match.
+    pub fn start_match(&mut self) {
+        self.left_score = 0;
+        self.right_score = 0;
+        self.left_rounds = 0;
+        self.right_rounds = 0;
+    }
+
+    // Whether there's a match part way through.
+    pub fn match_underway(&self) -> bool {
+        self.left_rounds + self.right_rounds > 0
+    }
+
+    // Chalking up the round that just finished. Hands back the match's winner, once someone's
+    // taken enough rounds, a knockout's taken the whole thing, or a survival run's over.
+    pub fn record_round(&mut self) -> Option<Side> {
+        let winner = match self.phase {
+            Phase::LeftWin => Side::Left,
+            Phase::RightWin => Side::Right,
+            _ => return None,
+        };
+        let rounds = match winner {
+            Side::Left => &mut self.left_rounds,
+            Side::Right => &mut self.right_rounds,
+        };
+        *rounds += 1;
+        let decided = *rounds >= self.points_to_win
+            || (self.knocked_out && self.knockout == KnockoutRule::Match)
+            || self.mode == GameMode::Survival;
+        decided.then_some(winner)
+    }
+}
+
+// The controls for a paddle, keyboard ones depending on which side it's on.
+pub fn paddle_control(controller: Controller, left_side: bool) -> ControlType {
+    match (controller, left_side) {
+        (Controller::Ai, _) => ControlType::AI(0.0),
+        (Controller::Human, true) => ControlType::Player(
+            Controls {
+                up: vec![KeyCode::W],
+                left: vec![KeyCode::A],
+                down: vec![KeyCode::S],
+                right: vec![KeyCode::D],
+                strafe_left: vec![KeyCode::Q],
+                strafe_right: vec![KeyCode::E],
+                dash: vec![KeyCode::LeftShift],
+                parry: vec![KeyCode::F],
+            },
+            0.0,
+        ),
+        (Controller::Human, false) => ControlType::Player(
+            Controls {
+                up: vec![KeyCode::Up],
+                left: vec![KeyCode::Left],
+                down: vec![KeyCode::Down],
+                right: vec![KeyCode::Right],
+                strafe_left: vec![KeyCode::Comma],
+                strafe_right: vec![KeyCode::Period],
+                dash: vec![KeyCode::RightShift],
+                parry: vec![KeyCode::RightControl],
+            },
+            0.0,
+        ),
+    }
+}
+
+pub fn world_reset(world: &mut World, level: &Level, options: &Options) {
+    world.clear(); // Resetting the world.
+    spawn_obstacles(world, level);
+    world.spawn((
+        Transform {
+            position: level.spawn_point(SpawnKind::Ball),
+            velocity: (0.0, 0.0),
+        },
+        BallSpawn,
+    ));
+    // Our left paddle.
+    world.spawn((
+        Transform {
+            position: level.spawn_point(SpawnKind::Left),
+            velocity: (0.0, 0.0),
+        },
+        Bounds(PADDLE_RADIUS, PADDLE_LENGTH),
+        PaddleHealth::default(),
+        paddle_control(options.left, true),
+        TimedEffects::default(),
+        PaddleActions::new(left_zone()),
+        Sprite::new(PADDLE_SPRITE),
+    ));
+    // Our right paddle.
+    world.spawn((
+        Transform {
+            position: level.spawn_point(SpawnKind::Right),
+            velocity: (0.0, 0.0),
+        },
+        Bounds(PADDLE_RADIUS, PADDLE_LENGTH),
+        PaddleHealth::default(),
+        paddle_control(options.right, false),
+        TimedEffects::default(),
+        PaddleActions::new(right_zone()),
+        Sprite::new(PADDLE_SPRITE).flipped(),
+    ));
+}
+
+// Serving a fresh ball from the level's spawn point (or the middle of the arena.)
+pub fn spawn_ball(world: &mut World, direction: (f32, f32)) -> Entity {
+    let start_speed = arena_width() / 1280.0;
+    let position = world
+        .query::<(&Transform, &BallSpawn)>()
+        .iter()
+        .map(|(_e, (transform, _spawn))| transform.position)
+        .next()
+        .unwrap_or((arena_width() / 2.0, arena_height() / 2.0));
+    world.spawn((
+        Transform {
+            position,
+            velocity: (direction.0 * start_speed, direction.1 * start_speed),
+        },
+        Ball {
+            radius: 16.0,
+            speed: start_speed,
+            ..Default::default()
+        },
+        Sprite::new(BALL_SPRITE),
+    ))
+}
+
+// Sharing out the time between whoever touched each ball last.
+pub fn track_possession(world: &World, match_stats: &mut MatchStats, delta: f32) {
+    let sides = paddle_sides(world);
+    let holders = world
+        .query::<&Ball>()
+        .iter()
+        .map(|(_id, ball)| side_of(&sides, ball.last_hit))
+        .collect::<Vec<_>>();
+    for side in holders.iter().flatten() {
+        match_stats.side_mut(*side).possession += delta / holders.len() as f32;
+    }
+    match_stats.duration += delta;
+}
+
+// A survival run's score: every ball the players sent back.
+pub fn survival_score(world: &World, match_stats: &MatchStats) -> u32 {
+    player_sides(world)
+        .iter()
+        .map(|side| match_stats.side(*side).returns)
+        .sum()
+}
+
+// Survival's over once a goal goes in against someone playing. Returns the AI's win, if so.
+pub fn survival_result(world: &World, round: &RoundState) -> Option<Phase> {
+    let scorer = round.last_scorer?;
+    let conceded = match scorer {
+        Phase::LeftWin => Side::Right,
+        Phase::RightWin => Side::Left,
+        _ => return None,
+    };
+    player_sides(world).contains(&conceded).then_some(scorer)
+}
+
+// Serving at whoever's playing, sped up for however far into the run it is.
+pub fn serve_survival_ball(world: &mut World, speed_scale: f32) {
+    let toward = if player_sides(world).contains(&Side::Left) {
+        -1.0
+    } else {
+        1.0
+    };
+    let direction = random_serve_direction();
+    let ball = spawn_ball(world, (direction.0.abs() * toward, direction.1));
+    if let Ok((transform, ball)) = world.query_one_mut::<(&mut Transform, &mut Ball)>(ball) {
+        ball.speed *= speed_scale;
+        transform.velocity = (
+            transform.velocity.0 * speed_scale,
+            transform.velocity.1 * speed_scale,
+        );
+    }
+}
+
+// Which sides have someone playing.
+pub fn player_sides(world: &World) -> Vec<Side> {
+    world
+        .query::<(&Transform, &ControlType)>()
+        .iter()
+        .filter(|(_id, (_transform, controls))| matches!(controls, ControlType::Player(..)))
+        .map(|(_id, (transform, _controls))| Side::of(transform.position.0))
+        .collect()
+}
+
+// A (normalised) serve towards a random side, at a slight angle.
+pub fn random_serve_direction() -> (f32, f32) {
+    let angle = rand::gen_range(-0.5_f32, 0.5);
+    let side = if rand::gen_range(0, 2) == 0 {
+        -1.0
+    } else {
+        1.0
+    };
+    (angle.cos() * side, angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Playing out rounds, each won by whoever's given.
+    fn play(game_state: &mut GameState, winners: &[Phase]) -> Vec<Option<Side>> {
+        winners
+            .iter()
+            .map(|&winner| {
+                game_state.phase = winner;
+                game_state.record_round()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_match_is_first_to_the_points() {
+        let mut game_state = GameState::new();
+        game_state.points_to_win = 3;
+        let results = play(
+            &mut game_state,
+            &[
+                Phase::LeftWin,
+                Phase::RightWin,
+                Phase::LeftWin,
+                Phase::RightWin,
+            ],
+        );
+        assert_eq!(results, [None; 4]);
+        assert!(game_state.match_underway());
+        assert_eq!(
+            play(&mut game_state, &[Phase::RightWin]),
+            [Some(Side::Right)]
+        );
+        assert_eq!((game_state.left_rounds, game_state.right_rounds), (2, 3));
+        game_state.start_match();
+        assert!(!game_state.match_underway());
+        assert_eq!((game_state.left_score, game_state.right_score), (0, 0));
+    }
+
+    #[test]
+    fn only_finished_rounds_count() {
+        let mut game_state = GameState::new();
+        assert_eq!(
+            play(&mut game_state, &[Phase::Start, Phase::Ongoing]),
+            [None; 2]
+        );
+        assert!(!game_state.match_underway());
+    }
+
+    #[test]
+    fn knockouts_only_take_the_match_under_ko_match() {
+        let mut game_state = GameState::new();
+        game_state.knocked_out = true;
+        game_state.knockout = KnockoutRule::Point;
+        assert_eq!(play(&mut game_state, &[Phase::LeftWin]), [None]);
+        game_state.knockout = KnockoutRule::Match;
+        assert_eq!(play(&mut game_state, &[Phase::LeftWin]), [Some(Side::Left)]);
+    }
+
+    #[test]
+    fn a_survival_run_is_a_match_by_itself() {
+        let mut game_state = GameState::new();
+        game_state.mode = GameMode::Survival;
+        assert_eq!(
+            play(&mut game_state, &[Phase::RightWin]),
+            [Some(Side::Right)]
+        );
+    }
+}
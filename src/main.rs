@@ -1,6 +1,7 @@
 use hecs::*;
 use macroquad::prelude::*;
 use rodio::*;
+use std::path::{Path, PathBuf};
 
 // And our constants.
 const MAX_VOLUME: f32 = 0.1;
@@ -17,219 +18,17 @@ macro_rules! play_audio {
     };
 }
 
-// Tracking the phases of a game.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-enum Phase {
-    Start,
-    Ongoing,
-    LeftWin,
-    RightWin,
-}
-
-// Implementing the default phase.
-impl Default for Phase {
-    fn default() -> Self {
-        Phase::Start
-    }
-}
-
-// A component to store an objects position and velocity.
-#[derive(Default, Clone, Copy)]
-struct Transform {
-    position: (f32, f32),
-    velocity: (f32, f32),
-}
-
-// A component to store an object's bounds (for collision testing.)
-#[derive(Default, Clone, Copy)]
-struct Bounds(f32, f32); // (radius, length) Mostly here as a reminder.
-
-#[derive(Default, Clone)]
-struct Controls {
-    up: Vec<KeyCode>,
-    left: Vec<KeyCode>,
-    down: Vec<KeyCode>,
-    right: Vec<KeyCode>,
-}
-
-// For tracking the controls of a given entity. (Also bullet cooldowns)
-enum ControlType {
-    AI(f64),
-    Player(Controls, f64),
-}
-
-// The ball!
-#[derive(Default, Clone, Copy)]
-struct Ball {
-    radius: f32,
-    speed: f32,
-}
-
-#[derive(Default, Clone, Copy)]
-struct Bullet {
-    radius: f32,
-}
-
-// The game state as a whole.
-#[derive(Default, Clone, Copy)]
-struct GameState {
-    phase: Phase,
-    left_score: i32,
-    right_score: i32,
-    intensity: f32,
-    target_color: Color,
-    current_color: Color,
-    hitstun: i32,
-}
-
-// Creating a constructor for it.
-impl GameState {
-    fn new() -> Self {
-        GameState {
-            phase: Phase::Start,
-            left_score: 0,
-            right_score: 0,
-            intensity: 0.0,
-            target_color: BLACK,
-            current_color: BLACK,
-            hitstun: 0,
-        }
-    }
-}
-
-#[derive(Default, Clone, Copy)]
-struct Particle {
-    position: (f32, f32),
-    velocity: (f32, f32),
-    size: f32,
-    color: Color,
-    birthtime: f64,
-    deathtime: f64,
-}
-
-#[derive(Default, Clone)]
-struct ParticleStorage {
-    particles_container: Vec<Particle>,
-}
-
-impl ParticleStorage {
-    fn new() -> Self {
-        Self {
-            particles_container: Vec::new(),
-        }
-    }
-
-    fn create_particle(
-        &mut self,
-        count: i32,
-        position: (f32, f32),
-        velocity: (f32, f32),
-        size: f32,
-        color: Color,
-        age: f64,
-        position_variance: (f32, f32),
-        velocity_variance: (f32, f32),
-        size_variance: f32,
-        age_variance: f64,
-    ) {
-        let curr_time = macroquad::time::get_time();
-        for _i in 0..count {
-            self.particles_container.push(Particle {
-                position: (
-                    position.0
-                        + rand::RandomRange::gen_range(-position_variance.0, position_variance.0),
-                    position.1
-                        + rand::RandomRange::gen_range(-position_variance.1, position_variance.1),
-                ),
-                velocity: (
-                    velocity.0
-                        + rand::RandomRange::gen_range(-velocity_variance.0, velocity_variance.0),
-                    velocity.1
-                        + rand::RandomRange::gen_range(-velocity_variance.1, velocity_variance.1),
-                ),
-                size: size + rand::RandomRange::gen_range(-size_variance, size_variance),
-                color: color,
-                birthtime: curr_time,
-                deathtime: curr_time
-                    + age
-                    + rand::RandomRange::gen_range(-age_variance, age_variance),
-            })
-        }
-    }
-}
-
-fn world_reset(world: &mut World) {
-    world.clear(); // Resetting the world.
-                   // Our left paddle.
-    world.spawn((
-        Transform {
-            position: (64.0, screen_height() / 2.0),
-            velocity: (0.0, 0.0),
-        },
-        Bounds(16.0, 64.0),
-        ControlType::Player(
-            Controls {
-                up: vec![KeyCode::W],
-                left: vec![KeyCode::A],
-                down: vec![KeyCode::S],
-                right: vec![KeyCode::D],
-            },
-            0.0,
-        ),
-    ));
-    // Our right paddle.
-    world.spawn((
-        Transform {
-            position: (screen_width() - 64.0, screen_height() / 2.0),
-            velocity: (0.0, 0.0),
-        },
-        Bounds(16.0, 64.0),
-        ControlType::AI(0.0),
-    ));
-}
-
-fn square_distance(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
-    (x1 - x2).powf(2.0) + (y1 - y2).powf(2.0)
-}
-
-// Returns the squared distance between point c and segment ab
-fn square_distance_point_segment(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
-    let ab = (b.0 - a.0, b.1 - a.1); // Getting our distance vectors.
-    let ac = (c.0 - a.0, c.1 - a.1);
-    let bc = (c.0 - b.0, c.1 - b.1);
-    let e = ac.0 * ab.0 + ac.1 * ab.1; // Getting the dot product for the central thingy.
-    if e <= 0.0 {
-        return ac.0 * ac.0 + ac.1 * ac.1;
-    } // Handle cases where c projects outside ab
-    let f = ab.0 * ab.0 + ab.1 * ab.1;
-    if e >= f {
-        return bc.0 * bc.0 + bc.1 * bc.1;
-    } // Handle cases where c projects onto ab
-    (ac.0 * ac.0 + ac.1 * ac.1) - e * e / f
-}
-
-fn test_sphere_capsule(sphere: (&Transform, &Ball), capsule: (&Transform, &Bounds)) -> bool {
-    // Compute (squared) distance between sphere center and capsule line segment
-    let dist2 = square_distance_point_segment(
-        (
-            capsule.0.position.0,
-            capsule.0.position.1 + (capsule.1 .1 / 2.0),
-        ),
-        (
-            capsule.0.position.0,
-            capsule.0.position.1 - (capsule.1 .1 / 2.0),
-        ),
-        sphere.0.position,
-    );
-    // If (squared) distance smaller than (squared) sum of radii, they collide
-    dist2 <= (sphere.1.radius + capsule.1 .0).powf(2.0)
-}
+use pong_with_guns::*;
 
 // Setting Window Configurations.
 fn config() -> Conf {
+    let options = launch_options();
+    let (window_width, window_height) = options.resolution.unwrap_or((800, 600));
     Conf {
         window_title: "Pong with Guns".to_string(),
-        fullscreen: true,
+        fullscreen: !options.windowed,
+        window_width,
+        window_height,
         ..Default::default()
     }
 }
@@ -237,9 +36,23 @@ fn config() -> Conf {
 // Main!
 #[macroquad::main(config)]
 async fn main() {
+    let options = launch_options(); // However we were launched.
     let mut game_state = GameState::new(); // Creating the new gamestate.
+    if let Some(mode) = options.mode {
+        game_state.mode = mode;
+    }
+    if let Some(seed) = options.seed {
+        rand::srand(seed);
+    }
     let mut world = World::new(); // For storing all of our entities. :)
-    let mut particles = ParticleStorage::new(); // Here is this funny thing.
+    let mut particles = ParticleStorage::new(DEFAULT_PARTICLE_BUDGET, load_emitters()); // Here is this funny thing.
+    let mut post_processing = PostProcessing::new(); // And some shaders on top.
+    let mut camera = GameCamera::new(); // For throwing the view around.
+    let mut hud = Hud::load(HUD_FONT_FILE).await; // And the text over the top.
+    let mut debug = DebugOverlay::new(); // And what's going on under the hood.
+    let mut console = Console::new(); // And for messing with it.
+    let mut clock = GameClock::new(); // And the game's own time, which can slow down or stop.
+    let sprites = SpriteAtlas::load(SPRITE_ATLAS_FILE).await; // Skins, if there are any.
     let mut frame_count = 0_u64;
 
     // Music stuff.
@@ -249,6 +62,9 @@ async fn main() {
     let sink_synth = Sink::try_new(&stream_handle).unwrap();
     let sink_vocals = Sink::try_new(&stream_handle).unwrap();
     let sink_sfx = Sink::try_new(&stream_handle).unwrap();
+    if options.mute {
+        sink_sfx.set_volume(0.0);
+    }
 
     let mut target_volume_bass;
     let mut target_volume_drums;
@@ -260,29 +76,58 @@ async fn main() {
     let mut current_volume_synth = 0.0;
     let mut current_volume_vocals = 0.0;
 
-    particles.create_particle(
-        125,
-        (screen_width() / 2.0, screen_height() / 2.0),
-        (0.0, 0.4),
-        2.0,
-        WHITE,
-        60.0,
+    let stars = particles
+        .preset("ambient_stars")
+        .count(125)
+        .spread(screen_width() / 2.0, screen_height() / 2.0);
+    particles.emit(
+        &stars,
         (screen_width() / 2.0, screen_height() / 2.0),
-        (0.0, 0.2),
-        0.0,
-        0.0,
+        (0.0, 0.0),
     );
 
-    world_reset(&mut world);
+    // Our arenas.
+    let mut levels = available_levels();
+    let mut level_index = 0;
+    let mut level = match levels.first() {
+        Some(path) => Level::load_or_default(path),
+        None => Level::default(),
+    };
+
+    world_reset(&mut world, &level, options);
+
+    // And the editor, once it's been opened.
+    let mut editor: Option<Editor> = None;
+    let mut editing = false;
+    let mut serve_now = false; // For jumping straight into a test game.
+    let mut test_game = false; // Test games don't count towards the profile, stats or leaderboard.
+
+    // The local player's record, for unlocking things.
+    let mut profile = Profile::load(Path::new(PROFILE_FILE));
+    let mut achievements = Achievements::new(load_achievements());
+    let mut last_phase = game_state.phase;
+
+    // How the current (or last) match went.
+    let mut match_stats = MatchStats::default();
+
+    // The best survival runs, and the one that's just gone up on the board.
+    let mut leaderboard = Leaderboard::load(Path::new(LEADERBOARD_FILE));
+    let mut initials: Option<InitialsEntry> = None;
+    let mut last_entry: Option<LeaderboardEntry> = None;
+    let mut survival_seed = 0_u64;
 
     'main: loop {
         // And for frame time.
-        let current_time = macroquad::time::get_time();
+        let real_time = macroquad::time::get_time(); // For menus and the like, which never slow down.
+        clock.update(&mut game_state, get_frame_time());
+        let current_time = clock.now();
+        let mut events: Vec<GameEvent> = Vec::new(); // For the achievements to look over.
         frame_count += 1; // This too.
-        let screenshake_offset = (
-            (frame_count as f32).sin() * game_state.hitstun as f32 / 2.0,
-            (frame_count as f32 * 0.1).sin() * game_state.hitstun as f32 / 2.0,
-        );
+        camera.reduced_motion = game_state.reduced_motion;
+        camera.update(get_frame_time());
+        hud.update();
+        debug.update(&mut world, &mut game_state, &camera, get_frame_time());
+        let theme = THEMES[game_state.theme];
 
         // Audio control, 'cause music is important.
         target_volume_bass = 1.0_f32;
@@ -303,10 +148,21 @@ async fn main() {
         current_volume_vocals = (current_volume_vocals * 0.9) + (target_volume_vocals * 0.1);
 
         // Actually setting the values
-        sink_bass.set_volume(current_volume_bass.clamp(0.0, MAX_VOLUME));
-        sink_drums.set_volume(current_volume_drums.clamp(0.0, MAX_VOLUME));
-        sink_synth.set_volume(current_volume_synth.clamp(0.0, MAX_VOLUME));
-        sink_vocals.set_volume(current_volume_vocals.clamp(0.0, MAX_VOLUME));
+        let max_volume = if options.mute { 0.0 } else { MAX_VOLUME };
+        sink_bass.set_volume(current_volume_bass.clamp(0.0, max_volume));
+        sink_drums.set_volume(current_volume_drums.clamp(0.0, max_volume));
+        sink_synth.set_volume(current_volume_synth.clamp(0.0, max_volume));
+        sink_vocals.set_volume(current_volume_vocals.clamp(0.0, max_volume));
+
+        // Slowing (or stopping) the music along with the game.
+        for sink in [&sink_bass, &sink_drums, &sink_synth, &sink_vocals] {
+            sink.set_speed(game_state.time_scale);
+            if clock.running() {
+                sink.play();
+            } else {
+                sink.pause();
+            }
+        }
 
         // Refreshing our samples if its empty.
         if sink_vocals.empty() {
@@ -333,19 +189,41 @@ async fn main() {
             sink_vocals.append(music_vocals);
         }
 
+        // The level editor takes over the whole screen while it's open.
+        if editing {
+            if let Some(editor) = editor.as_mut() {
+                let action = editor.update(real_time);
+                if let EditorAction::Leave | EditorAction::TestPlay = action {
+                    level = editor.level.clone();
+                    levels = available_levels();
+                    level_index = levels
+                        .iter()
+                        .position(|path| *path == editor.path)
+                        .unwrap_or(0);
+                    world_reset(&mut world, &level, options);
+                    game_state.phase = Phase::Start;
+                    serve_now = matches!(action, EditorAction::TestPlay);
+                    test_game = serve_now;
+                    editing = false;
+                }
+                editor.draw(real_time, &theme);
+            }
+            next_frame().await;
+            continue;
+        }
+
         // Handling Rendering.
         //
+        post_processing.begin();
+        set_camera(&camera.scene(post_processing.scene_target()));
+
         // Clearing our background.
-        game_state.target_color = Color {
-            r: game_state.intensity / 400.0
-                + (game_state.hitstun as f32 / 10.0).clamp(0.0, 0.1)
-                + (game_state.left_score as f32 / 50.0),
-            g: game_state.intensity / 400.0 + (game_state.hitstun as f32 / 10.0).clamp(0.0, 0.1),
-            b: game_state.intensity / 400.0
-                + (game_state.hitstun as f32 / 10.0).clamp(0.0, 0.1)
-                + (game_state.right_score as f32 / 50.0),
-            a: 1.0,
-        };
+        game_state.target_color = theme.background_for(
+            game_state.intensity,
+            camera.flash(),
+            game_state.left_score,
+            game_state.right_score,
+        );
         game_state.current_color = Color {
             r: game_state.current_color.r * 0.9 + game_state.target_color.r * 0.1,
             g: game_state.current_color.g * 0.9 + game_state.target_color.g * 0.1,
@@ -355,175 +233,353 @@ async fn main() {
         clear_background(game_state.current_color);
 
         // Particles, since these are background items.
-        particles.particles_container.iter_mut().for_each(|part| {
-            draw_circle(
-                part.position.0,
-                part.position.1,
-                clamp(
-                    part.size
-                        * ((current_time - part.deathtime) / (part.birthtime - part.deathtime))
-                            .clamp(0.0, 1.0) as f32,
-                    0.0,
-                    f32::MAX,
-                ),
-                Color {
-                    r: part.color.r,
-                    g: part.color.g,
-                    b: part.color.b,
-                    a: part.color.a,
-                },
-            );
-
-            part.position = (
-                part.position.0 + part.velocity.0,
-                part.position.1 + part.velocity.1,
-            )
-        });
-        particles
-            .particles_container
-            .retain(|&part| part.deathtime > current_time);
+        particles.update(clock.delta());
+        particles.draw();
 
         // Current Phase text.
         {
-            let phase_text = match game_state.phase {
-                Phase::Start => "Waiting for Spacebar.",
-                Phase::Ongoing => "Game ahoy!",
-                Phase::LeftWin => "Left wins!",
-                Phase::RightWin => "Right wins!",
+            let phase_text = match (game_state.phase, game_state.mode) {
+                (Phase::Start, _) => "Waiting for Spacebar.".to_string(),
+                (Phase::Ongoing, GameMode::Classic) => "Game ahoy!".to_string(),
+                (Phase::Ongoing, GameMode::Survival) => {
+                    let elapsed = current_time - game_state.round.started_at;
+                    format!(
+                        "Survival level {}  {} returns  {:.0}s",
+                        survival_level(elapsed),
+                        survival_score(&world, &match_stats),
+                        elapsed
+                    )
+                }
+                (Phase::LeftWin | Phase::RightWin, GameMode::Survival) => format!(
+                    "Survived {:.0}s with {} returns.",
+                    match_stats.duration,
+                    survival_score(&world, &match_stats)
+                ),
+                (Phase::Ongoing, GameMode::Multiball) => match game_state.rules.round_end {
+                    RoundEnd::TimeLimit(seconds) => format!(
+                        "Rally {} - {} ({}s)",
+                        game_state.round.left_goals,
+                        game_state.round.right_goals,
+                        (seconds - (current_time - game_state.round.started_at))
+                            .max(0.0)
+                            .ceil()
+                    ),
+                    _ => format!(
+                        "Rally {} - {}",
+                        game_state.round.left_goals, game_state.round.right_goals
+                    ),
+                },
+                // Partway through the match, just the round.
+                (Phase::LeftWin | Phase::RightWin, _) if !match_stats.finished() => format!(
+                    "{} takes the round{}. {} - {}, first to {}.",
+                    if game_state.phase == Phase::LeftWin {
+                        "Left"
+                    } else {
+                        "Right"
+                    },
+                    if game_state.knocked_out {
+                        " by knockout"
+                    } else {
+                        ""
+                    },
+                    game_state.left_rounds,
+                    game_state.right_rounds,
+                    game_state.points_to_win
+                ),
+                (Phase::LeftWin, _) if game_state.knocked_out => {
+                    "Left wins the match by knockout!".to_string()
+                }
+                (Phase::RightWin, _) if game_state.knocked_out => {
+                    "Right wins the match by knockout!".to_string()
+                }
+                (Phase::LeftWin, _) => "Left wins the match!".to_string(),
+                (Phase::RightWin, _) => "Right wins the match!".to_string(),
             };
-            let text_horizontal_pos =
-                (screen_width() / 2.0) - (measure_text(&phase_text, None, 32, 1.0).width / 2.0);
-            draw_text(
-                &phase_text,
-                text_horizontal_pos + screenshake_offset.0,
-                64.0 + screenshake_offset.1,
-                32.0,
-                WHITE,
-            );
-            let score_text = format!("{} - {}", game_state.left_score, game_state.right_score);
-            let text_horizontal_pos =
-                (screen_width() / 2.0) - (measure_text(&score_text, None, 32, 1.0).width / 2.0);
-            draw_text(
-                &score_text,
-                text_horizontal_pos + screenshake_offset.0,
-                screen_height() - 64.0 + screenshake_offset.1,
-                32.0,
-                WHITE,
-            );
-            let speed_text = format!("{}", game_state.intensity.round().abs());
-            let text_horizontal_pos =
-                (screen_width() / 2.0) - (measure_text(&speed_text, None, 32, 1.0).width / 2.0);
-            draw_text(
-                &speed_text,
-                text_horizontal_pos + screenshake_offset.0,
-                screen_height() - 32.0 + screenshake_offset.1,
-                32.0,
-                WHITE,
-            );
+            hud.banner(&phase_text, &theme);
+            // And what can be changed between rounds.
+            if game_state.phase != Phase::Ongoing {
+                let mut options_text = format!("[M] {}", game_state.mode.label());
+                if game_state.mode == GameMode::Multiball {
+                    options_text += &format!("  [R] {}", game_state.rules.round_end.label());
+                }
+                options_text += &format!(
+                    "  [K] {}  [H] {}  [L] {}  [P] {} particles  [Tab] editor",
+                    game_state.knockout.label(),
+                    if game_state.horizontal_movement {
+                        "free move"
+                    } else {
+                        "fixed"
+                    },
+                    level.name,
+                    particles.budget()
+                );
+                let effects_text = Effect::ALL
+                    .iter()
+                    .map(|effect| {
+                        format!(
+                            "[{:?}] {} {}",
+                            effect.key(),
+                            effect.label(),
+                            post_processing.status(*effect)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    + &format!(
+                        "  [T] {}  [O] {} motion",
+                        theme.name,
+                        if game_state.reduced_motion {
+                            "reduced"
+                        } else {
+                            "full"
+                        }
+                    );
+                let (unlocked, unlockable) = profile.unlock_count();
+                let (earned, achievable) = achievements.progress(&profile);
+                let profile_text = format!(
+                    "[Z] {} paddle  [X] {} trail  [N] {} bullets  ({} wins, {} games, {}/{} unlocked, {}/{} achievements)",
+                    profile.paddle_skin().name,
+                    profile.ball_trail().name,
+                    profile.bullet_colour().name,
+                    profile.wins,
+                    profile.games_played,
+                    unlocked,
+                    unlockable,
+                    earned,
+                    achievable
+                );
+                hud.options(&[options_text, effects_text, profile_text], &theme);
+            }
+            hud.score(game_state.left_score, game_state.right_score, &theme);
+            hud.intensity(game_state.intensity, &theme);
+            // And how the last match went, once it's over, or the leaderboard for survival.
+            if game_state.phase != Phase::Ongoing && game_state.mode == GameMode::Survival {
+                match &initials {
+                    Some(entry) => entry.draw(&hud, &theme, real_time),
+                    None => leaderboard.draw(&hud, &theme, last_entry.as_ref()),
+                }
+            } else if game_state.phase != Phase::Ongoing && match_stats.finished() {
+                match_stats.draw(&hud, &theme);
+            }
         }
 
         // DRAWING SYSTEM
-        for (_id, (transform, _ball)) in world.query_mut::<(&Transform, &Bullet)>() {
-            // Drawing the bullet.
-            draw_circle(
-                transform.position.0 + screenshake_offset.0,
-                transform.position.1 + screenshake_offset.1,
-                8.0,
-                BLACK,
-            );
-            draw_circle(
-                transform.position.0 + screenshake_offset.0,
-                transform.position.1 + screenshake_offset.1,
-                4.0,
-                WHITE,
-            );
+        draw_goals(&level, &theme);
+        draw_obstacles(&mut world, &theme);
+
+        let players = world
+            .query::<&ControlType>()
+            .iter()
+            .filter(|(_id, controls)| matches!(controls, ControlType::Player(..)))
+            .map(|(id, _controls)| id)
+            .collect::<Vec<_>>();
+        for (_id, (transform, bullet, sprite)) in
+            world.query_mut::<(&Transform, &Bullet, Option<&Sprite>)>()
+        {
+            // The player's own bullets come out in their colour.
+            let color = match profile.bullet_colour().look {
+                Some(color) if bullet.owner.is_some_and(|owner| players.contains(&owner)) => color,
+                _ => theme.foreground,
+            };
+            // Drawing the bullet, pointing the way it's going.
+            let angle = transform.velocity.1.atan2(transform.velocity.0);
+            let skinned = sprite.is_some_and(|sprite| {
+                sprites.draw(
+                    sprite,
+                    transform.position,
+                    (16.0, 16.0),
+                    angle,
+                    current_time,
+                )
+            });
+            if !skinned {
+                draw_circle(
+                    transform.position.0,
+                    transform.position.1,
+                    8.0,
+                    theme.shadow,
+                );
+                draw_circle(transform.position.0, transform.position.1, 4.0, color);
+            }
         }
 
+        // Power-ups, and whatever the balls are carrying (drawn under the balls.)
+        draw_power_ups(&mut world, current_time, &theme, &sprites);
+
         //
         // Handling balls.
-        for (_id, (transform, ball)) in world.query_mut::<(&Transform, &Ball)>() {
-            // Drawing the ball outline.
-            draw_circle_lines(
-                transform.position.0 + screenshake_offset.0,
-                transform.position.1 + screenshake_offset.1,
-                ball.radius,
-                2.0,
-                WHITE,
-            );
-            // Drawing the ball.
-            draw_circle(
-                transform.position.0 + screenshake_offset.0,
-                transform.position.1 + screenshake_offset.1,
-                2.0,
-                BLACK,
-            );
+        for (_id, (transform, ball, sprite)) in
+            world.query_mut::<(&Transform, &Ball, Option<&Sprite>)>()
+        {
+            let size = ball.radius * 2.0;
+            let skinned = sprite.is_some_and(|sprite| {
+                sprites.draw(sprite, transform.position, (size, size), 0.0, current_time)
+            });
+            if !skinned {
+                // Drawing the ball outline.
+                draw_circle_lines(
+                    transform.position.0,
+                    transform.position.1,
+                    ball.radius,
+                    2.0,
+                    theme.foreground,
+                );
+                // Drawing the ball.
+                draw_circle(
+                    transform.position.0,
+                    transform.position.1,
+                    2.0,
+                    theme.shadow,
+                );
+            }
+            // And how it's spinning.
+            draw_spin(transform, ball, &theme);
         }
 
         // Handling Paddles
-        for (_id, (transform, bounds)) in world.query_mut::<(&Transform, &Bounds)>() {
-            draw_rectangle(
-                transform.position.0 - bounds.0,
-                transform.position.1 - bounds.1,
-                bounds.0 * 2.0,
-                bounds.1 * 2.0,
-                BLACK,
-            );
-            draw_rectangle_lines(
-                transform.position.0 - bounds.0,
-                transform.position.1 - bounds.1,
-                bounds.0 * 2.0,
-                bounds.1 * 2.0,
-                4.0,
-                WHITE,
-            );
+        for (_id, (transform, bounds, health, sprite, controls)) in world.query_mut::<(
+            &Transform,
+            &Bounds,
+            Option<&PaddleHealth>,
+            Option<&Sprite>,
+            Option<&ControlType>,
+        )>() {
+            let state = match health {
+                Some(health) => health.state(bounds),
+                None => DamageState::Healthy,
+            };
+            let color = damage_color(state, current_time, &theme, !game_state.reduced_motion);
+            // Skinned paddles still get their damage colour, mixed into the tint.
+            let skinned = sprite.is_some_and(|sprite| {
+                let tint = Color::new(
+                    sprite.tint.r * color.r,
+                    sprite.tint.g * color.g,
+                    sprite.tint.b * color.b,
+                    sprite.tint.a * color.a,
+                );
+                sprites.draw(
+                    &sprite.tint(tint),
+                    transform.position,
+                    (bounds.0 * 2.0, bounds.1 * 2.0),
+                    0.0,
+                    current_time,
+                )
+            });
+            if !skinned {
+                // The player's paddle gets their skin.
+                let style = match controls {
+                    Some(ControlType::Player(..)) => profile.paddle_skin().look,
+                    _ => PaddleStyle::Outline,
+                };
+                draw_paddle(style, transform.position, bounds, color, &theme);
+            }
+            // Crossing out anything that's been knocked out, corner to corner of its round ends.
+            if state == DamageState::Destroyed {
+                let shape = bounds.shape();
+                let (top, bottom) = shape.endpoints(transform.position);
+                let corner = shape.radius() * std::f32::consts::FRAC_1_SQRT_2;
+                draw_line(
+                    top.0 - corner,
+                    top.1 - corner,
+                    bottom.0 + corner,
+                    bottom.1 + corner,
+                    4.0,
+                    theme.dim,
+                );
+                draw_line(
+                    top.0 + corner,
+                    top.1 - corner,
+                    bottom.0 - corner,
+                    bottom.1 + corner,
+                    4.0,
+                    theme.dim,
+                );
+            }
         }
 
+        // Parries and cooldowns.
+        draw_actions(&mut world, current_time, &theme);
+        draw_cooldowns(&mut world, current_time, &hud, &theme);
+
+        // Shields and the effect timers.
+        draw_effects_hud(&mut world, current_time, &hud);
+
         // Handling Tutorial Text
         if game_state.phase != Phase::Ongoing {
             for (_id, (transform, controls, bounds)) in
                 world.query_mut::<(&Transform, &ControlType, &Bounds)>()
             {
-                let color = if ((current_time * 1.1) % 2.0) < 1.0 {
-                    WHITE
+                let color = if game_state.reduced_motion || ((current_time * 1.1) % 2.0) < 1.0 {
+                    theme.foreground
                 } else {
-                    GRAY
+                    theme.muted
                 };
                 match controls {
                     ControlType::Player(x, _c) => {
-                        draw_text(
+                        hud.label(
                             &format!("{:?}", &x.up[0]),
-                            transform.position.0 - 8.0,
-                            transform.position.1 - bounds.1 - 8.0,
+                            (
+                                transform.position.0 - 8.0,
+                                transform.position.1 - bounds.1 - 8.0,
+                            ),
                             36.0,
                             color,
                         );
-                        draw_text(
+                        hud.label(
                             &format!("{:?}", &x.down[0]),
-                            transform.position.0 - 8.0,
-                            transform.position.1 + bounds.1 + 26.0,
+                            (
+                                transform.position.0 - 8.0,
+                                transform.position.1 + bounds.1 + 26.0,
+                            ),
                             36.0,
                             color,
                         );
-                        draw_text(
+                        hud.label(
                             &format!("{:?}", &x.left[0]),
-                            transform.position.0 - bounds.0 - 24.0,
-                            transform.position.1 + 8.0,
+                            (
+                                transform.position.0 - bounds.0 - 24.0,
+                                transform.position.1 + 8.0,
+                            ),
                             36.0,
                             color,
                         );
-                        draw_text(
+                        hud.label(
                             &format!("{:?}", &x.right[0]),
-                            transform.position.0 + bounds.0 + 8.0,
-                            transform.position.1 + 8.0,
+                            (
+                                transform.position.0 + bounds.0 + 8.0,
+                                transform.position.1 + 8.0,
+                            ),
                             36.0,
                             color,
                         );
+                        hud.label(
+                            &format!("{:?} dash, {:?} parry", &x.dash[0], &x.parry[0]),
+                            (
+                                transform.position.0 - bounds.0,
+                                transform.position.1 + bounds.1 + 56.0,
+                            ),
+                            20.0,
+                            color,
+                        );
+                        if game_state.horizontal_movement {
+                            hud.label(
+                                &format!("{:?}/{:?} move", &x.strafe_left[0], &x.strafe_right[0]),
+                                (
+                                    transform.position.0 - bounds.0,
+                                    transform.position.1 + bounds.1 + 76.0,
+                                ),
+                                20.0,
+                                color,
+                            );
+                        }
                     }
                     ControlType::AI(_c) => {
-                        draw_text(
-                            &"AUTO",
-                            transform.position.0 - 32.0,
-                            transform.position.1 - bounds.1 - 8.0,
+                        hud.label(
+                            "AUTO",
+                            (
+                                transform.position.0 - 32.0,
+                                transform.position.1 - bounds.1 - 8.0,
+                            ),
                             36.0,
                             color,
                         );
@@ -532,412 +588,273 @@ async fn main() {
             }
         }
 
+        // The debug overlay, if it's up.
+        debug.draw_world(&world, &theme);
+        debug.draw_panels(&world, &game_state, &particles, &hud, &theme);
+
+        // Anything that's just been unlocked.
+        achievements.draw_toasts(&hud, &theme, real_time);
+        clock.draw(game_state.time_scale, &hud, &theme);
+        console.draw(&hud, &theme, real_time);
+
         // Handling Physics.
         //
-        // Braced for escaping the game.
-        if is_key_pressed(KeyCode::Escape) {
+        // Braced for escaping the game. (Unless that's just closing the console.)
+        if is_key_pressed(KeyCode::Escape) && !console.open {
             break 'main;
         }
 
-        // // Handling state changes.
-        if game_state.hitstun <= 0 {
-            if game_state.phase != Phase::Ongoing && is_key_pressed(KeyCode::Space) {
-                // And our ball.
-                let start_speed = screen_width() / 1280.0;
-                world.spawn((
-                    Transform {
-                        position: (screen_width() / 2.0, screen_height() / 2.0),
-                        velocity: (
-                            start_speed
-                                * (((game_state.phase != Phase::RightWin) as i32 as f32)
-                                    - ((game_state.phase == Phase::RightWin) as i32 as f32)),
-                            0.0,
-                        ),
-                    },
-                    Ball {
-                        radius: 16.0,
-                        speed: start_speed,
-                    },
-                ));
-                // Resetting the bounds of the paddles.
-                for (_id, (_transform, bounds)) in world.query_mut::<(&Transform, &mut Bounds)>() {
-                    bounds.0 = 16.0;
-                    bounds.1 = 64.0;
+        // The console, which takes the keyboard over while it's open.
+        console.update(&mut world, &mut game_state, &level, options, current_time);
+        let typing = initials.is_some() || console.open;
+
+        // Typing initials in for the leaderboard, which holds up the menu until it's done.
+        if let Some(entry) = initials.as_mut() {
+            if let Some(entry) = entry.update() {
+                leaderboard.insert(entry.clone());
+                if let Err(error) = leaderboard.save(Path::new(LEADERBOARD_FILE)) {
+                    eprintln!("Couldn't save the leaderboard. ({})", error);
                 }
-                // And finally, kicking everything off.
-                game_state.phase = Phase::Ongoing;
+                last_entry = Some(entry);
+                initials = None;
             }
+        }
 
-            // Let's pull a Mario 64.
-            for _i in 1..4 {
-                // Updating positions from velocities.
-                for (_id, transform) in world.query_mut::<&mut Transform>() {
-                    transform.position = (
-                        clamp(
-                            transform.position.0 + transform.velocity.0,
-                            -16.0,
-                            screen_width() + 16.0,
-                        ),
-                        clamp(
-                            transform.position.1 + transform.velocity.1,
-                            -16.0,
-                            screen_height() + 16.0,
-                        ),
-                    );
+        // // Handling state changes.
+        if !camera.frozen() {
+            // Picking the mode between rounds.
+            if game_state.phase != Phase::Ongoing && !typing {
+                let rules = (
+                    game_state.mode,
+                    game_state.rules.round_end,
+                    game_state.knockout,
+                    game_state.horizontal_movement,
+                    level_index,
+                );
+                if is_key_pressed(KeyCode::M) {
+                    game_state.mode = game_state.mode.next();
                 }
-
-                // Processing Paddles.
-                {
-                    let entities = world
-                        .query::<(&Transform, &Ball)>()
-                        .iter()
-                        .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-                        .collect::<Vec<_>>();
-                    let mut spawn_queue: Vec<(Transform, Bullet)> = Vec::new();
-                    for (_id, (transform, control)) in
-                        world.query_mut::<(&mut Transform, &mut ControlType)>()
-                    {
-                        // Slowing things down just a bit, just to ease control.
-                        transform.velocity =
-                            (transform.velocity.0 * 0.95, transform.velocity.1 * 0.95);
-
-                        // Handling Controls
-                        match control {
-                            ControlType::Player(x, s) => {
-                                transform.velocity = (
-                                    transform.velocity.0,
-                                    transform.velocity.1
-                                        + ((is_key_down(x.down[0]) as i32 as f32)
-                                            - (is_key_down(x.up[0]) as i32 as f32))
-                                            * 0.3,
-                                );
-                                if (is_key_down(x.right[0]) ^ is_key_down(x.left[0]))
-                                    && current_time > *s
-                                {
-                                    *s = current_time + 0.35;
-                                    spawn_queue.push((
-                                        Transform {
-                                            position: (
-                                                transform.position.0
-                                                    + ((is_key_down(x.right[0]) as i32 as f32)
-                                                        - (is_key_down(x.left[0]) as i32 as f32))
-                                                        * 32.0,
-                                                transform.position.1,
-                                            ),
-                                            velocity: (
-                                                (((is_key_down(x.right[0]) as i32 as f32)
-                                                    - (is_key_down(x.left[0]) as i32 as f32))
-                                                    * 2.0),
-                                                rand::RandomRange::gen_range(-0.1, 0.1),
-                                            ),
-                                        },
-                                        Bullet { radius: 2.0 },
-                                    ));
-                                    play_audio!(
-                                        sink_sfx,
-                                        "assets/sfx/bullet_shot.wav",
-                                        0.05,
-                                        rand::RandomRange::gen_range(0.9, 1.0)
-                                    );
-                                }
-                            }
-                            ControlType::AI(mut _s) => {
-                                if entities.first().is_some() {
-                                    let (mut target, mut target_distance) = (entities[0], f32::MAX);
-                                    for (id, ball_transform, ball_ball) in &entities {
-                                        let temp_distance = square_distance(
-                                            transform.position.0,
-                                            transform.position.1,
-                                            ball_transform.position.0,
-                                            ball_transform.position.1,
-                                        );
-                                        if temp_distance < target_distance {
-                                            target = (*id, *ball_transform, *ball_ball); // Setting the current target.
-                                            target_distance = temp_distance;
-                                        }
-                                    }
-                                    transform.velocity =
-                                        (
-                                            transform.velocity.0,
-                                            transform.velocity.1
-                                                + ((((transform.position.1 < target.1.position.1)
-                                                    as i32
-                                                    as f32)
-                                                    - ((transform.position.1 > target.1.position.1)
-                                                        as i32
-                                                        as f32))
-                                                    * (60.0 * target_distance.sqrt()
-                                                        / screen_width()))
-                                                .clamp(-0.25, 0.25),
-                                        )
-                                }
-                            }
-                        }
-
-                        // Porbatabled.
-                        particles.create_particle(
-                            1,
-                            transform.position,
-                            (0.0, 0.0),
-                            16.0,
-                            BLACK,
-                            0.5,
-                            (0.0, 0.0),
-                            (0.2, 0.2),
-                            0.0,
-                            0.0,
-                        );
+                if is_key_pressed(KeyCode::R) && game_state.mode == GameMode::Multiball {
+                    game_state.rules.round_end = game_state.rules.round_end.next();
+                }
+                if is_key_pressed(KeyCode::K) {
+                    game_state.knockout = game_state.knockout.next();
+                }
+                if is_key_pressed(KeyCode::H) {
+                    game_state.horizontal_movement = !game_state.horizontal_movement;
+                }
+                for effect in Effect::ALL {
+                    if is_key_pressed(effect.key()) {
+                        post_processing.toggle(effect);
                     }
-                    world.spawn_batch(spawn_queue);
                 }
-
-                // Bullet stuff.
-                {
-                    let mut bullet_has_collided: Vec<&Entity> = Vec::new();
-                    let bullets: Vec<(Entity, Transform, Bullet)> = world
-                        .query::<(&Transform, &Bullet)>()
+                if is_key_pressed(KeyCode::T) {
+                    game_state.theme = (game_state.theme + 1) % THEMES.len();
+                }
+                if is_key_pressed(KeyCode::O) {
+                    game_state.reduced_motion = !game_state.reduced_motion;
+                    particles.set_density(if game_state.reduced_motion {
+                        REDUCED_MOTION
+                    } else {
+                        1.0
+                    });
+                }
+                if is_key_pressed(KeyCode::P) {
+                    let next = PARTICLE_BUDGETS
                         .iter()
-                        .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-                        .collect::<Vec<_>>();
-                    for bullet in &bullets {
-                        for (_id, (transform, ball)) in
-                            world.query_mut::<(&mut Transform, &mut Ball)>()
-                        {
-                            if square_distance(
-                                bullet.1.position.0,
-                                bullet.1.position.1,
-                                transform.position.0,
-                                transform.position.1,
-                            ) < ball.radius.powf(2.0)
-                            {
-                                transform.velocity = (
-                                    (transform.position.0 - bullet.1.position.0) / 2.0
-                                        + (bullet.1.velocity.0 * 0.25),
-                                    (transform.position.1 - bullet.1.position.1) / 2.0
-                                        + (bullet.1.velocity.1 * 0.25),
-                                );
-                                let magnitude = (transform.velocity.0.powf(2.0)
-                                    + transform.velocity.1.powf(2.0))
-                                .sqrt();
-                                transform.velocity = (
-                                    (transform.velocity.0 / magnitude) * ball.speed,
-                                    (transform.velocity.1 / magnitude) * ball.speed,
-                                );
-                                particles.create_particle(
-                                    3,
-                                    bullet.1.position,
-                                    (transform.velocity.0 * 2.0, transform.velocity.1 * 2.0),
-                                    8.0,
-                                    WHITE,
-                                    0.3,
-                                    (0.1, 0.1),
-                                    (4.0, 8.0),
-                                    0.50,
-                                    0.25,
-                                );
-                                bullet_has_collided.push(&bullet.0);
-                                play_audio!(
-                                    sink_sfx,
-                                    "assets/sfx/ball_hit_side.wav",
-                                    0.05,
-                                    rand::RandomRange::gen_range(0.8, 1.0)
-                                );
-                            }
-                        }
-                        for (_id, (transform, bounds)) in
-                            world.query_mut::<(&mut Transform, &mut Bounds)>()
-                        {
-                            if test_sphere_capsule(
-                                (
-                                    &bullet.1,
-                                    &Ball {
-                                        radius: bullet.2.radius,
-                                        speed: 0.0,
-                                    },
-                                ),
-                                (transform, bounds),
-                            ) {
-                                bounds.1 -= 1.0;
-                                particles.create_particle(
-                                    3,
-                                    bullet.1.position,
-                                    (transform.velocity.0 * 2.0, transform.velocity.1 * 2.0),
-                                    8.0,
-                                    WHITE,
-                                    0.3,
-                                    (0.1, 0.1),
-                                    (4.0, 8.0),
-                                    0.50,
-                                    0.25,
-                                );
-                                bullet_has_collided.push(&bullet.0);
-                                play_audio!(
-                                    sink_sfx,
-                                    "assets/sfx/bullet_hit_paddle.wav",
-                                    0.05,
-                                    rand::RandomRange::gen_range(0.8, 1.0)
-                                );
-                            }
-                        }
-                    }
-                    for scrap in bullet_has_collided {
-                        world.despawn(*scrap).unwrap();
-                        game_state.hitstun += 1;
-                    }
+                        .position(|&budget| budget == particles.budget())
+                        .map_or(0, |index| (index + 1) % PARTICLE_BUDGETS.len());
+                    particles.set_budget(PARTICLE_BUDGETS[next]);
                 }
-
-                // Checking balls.
+                if is_key_pressed(KeyCode::Z) {
+                    profile.next_paddle_skin();
+                    profile.save_or_warn(Path::new(PROFILE_FILE));
+                }
+                if is_key_pressed(KeyCode::X) {
+                    profile.next_ball_trail();
+                    profile.save_or_warn(Path::new(PROFILE_FILE));
+                }
+                if is_key_pressed(KeyCode::N) {
+                    profile.next_bullet_colour();
+                    profile.save_or_warn(Path::new(PROFILE_FILE));
+                }
+                if is_key_pressed(KeyCode::L) && !levels.is_empty() {
+                    level_index = (level_index + 1) % levels.len();
+                    level = Level::load_or_default(&levels[level_index]);
+                    world_reset(&mut world, &level, options);
+                    editor = None;
+                    test_game = false;
+                }
+                // Changing the rules part way through a match starts it over.
+                if rules
+                    != (
+                        game_state.mode,
+                        game_state.rules.round_end,
+                        game_state.knockout,
+                        game_state.horizontal_movement,
+                        level_index,
+                    )
                 {
-                    let entities: Vec<(Entity, Transform, Bounds)> = world
-                        .query::<(&Transform, &Bounds)>()
-                        .iter()
-                        .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-                        .collect::<Vec<_>>();
-                    game_state.intensity = 0.0; // Resetting the intensity.
-                    for (_id, (transform, ball)) in world.query_mut::<(&mut Transform, &mut Ball)>()
-                    {
-                        // Doing the simple collision checks.
-                        if transform.position.0 > screen_width()
-                            && game_state.phase == Phase::Ongoing
-                        {
-                            game_state.phase = Phase::LeftWin;
-                            game_state.left_score += 1;
-                            particles.create_particle(
-                                100,
-                                transform.position,
-                                (-transform.velocity.0, -transform.velocity.1),
-                                4.0 * (transform.velocity.0.abs() + transform.velocity.1.abs()),
-                                RED,
-                                3.0,
-                                (0.1, 0.1),
-                                (
-                                    2.0 + transform.velocity.0.abs(),
-                                    8.0 + transform.velocity.0.abs(),
-                                ),
-                                1.0 * transform.velocity.0.abs(),
-                                1.0,
-                            );
-                            play_audio!(sink_sfx, "assets/sfx/ball_goal.wav", 1.0, 1.0);
-                            world.despawn(_id).unwrap();
-                            break;
+                    game_state.start_match();
+                }
+            }
+
+            // Opening the editor from the menu, or hopping back in from a test game.
+            if is_key_pressed(KeyCode::Tab)
+                && !typing
+                && (game_state.phase != Phase::Ongoing || test_game)
+            {
+                let path = levels
+                    .get(level_index)
+                    .cloned()
+                    .unwrap_or_else(|| PathBuf::from(LEVEL_DIRECTORY).join("custom.lvl"));
+                editor.get_or_insert_with(|| Editor::new(level.clone(), path));
+                game_state.phase = Phase::Start;
+                editing = true;
+            }
+
+            if game_state.phase != Phase::Ongoing
+                && !typing
+                && (is_key_pressed(KeyCode::Space) || serve_now)
+            {
+                // A new match, unless there's one part way through. (Test games start afresh, and
+                // so does the first real one after them.)
+                let fresh = serve_now
+                    || test_game
+                    || match_stats.finished()
+                    || !game_state.match_underway();
+                // Only the round served straight from the editor is a test.
+                test_game = serve_now;
+                serve_now = false;
+                // Survival runs get a fresh seed, so they can be looked up again later.
+                if game_state.mode == GameMode::Survival {
+                    survival_seed = options.seed.unwrap_or_else(unix_time);
+                    rand::srand(survival_seed);
+                }
+                start_round(&mut world, &mut game_state, current_time);
+                if fresh {
+                    game_state.start_match();
+                    match_stats = MatchStats::new(game_state.mode.label(), &level.name);
+                }
+            }
+
+            // Moving the game on, unless it's paused. (And not stepping a frame.)
+            if clock.running() {
+                let mut frame = Frame {
+                    current_time,
+                    delta: clock.delta(),
+                    level: &level,
+                    theme: &theme,
+                    particles: &mut particles,
+                    camera: &mut camera,
+                    match_stats: &mut match_stats,
+                    trail_tint: trail_tint(profile.ball_trail().look, current_time),
+                    typing,
+                    events: Vec::new(),
+                    sounds: Vec::new(),
+                };
+                step_physics(&mut world, &mut game_state, &mut frame);
+                events.append(&mut frame.events);
+                for (sound, volume, speed) in frame.sounds {
+                    let speed = speed * game_state.time_scale; // Pitching down with slow-mo.
+                    match sound {
+                        Sound::BallGoal => {
+                            play_audio!(sink_sfx, "assets/sfx/ball_goal.wav", volume, speed);
                         }
-                        if transform.position.0 < 0.0 && game_state.phase == Phase::Ongoing {
-                            game_state.phase = Phase::RightWin;
-                            game_state.right_score += 1;
-                            particles.create_particle(
-                                100,
-                                transform.position,
-                                (-transform.velocity.0, -transform.velocity.1),
-                                4.0 * (transform.velocity.0.abs() + transform.velocity.1.abs()),
-                                BLUE,
-                                3.0,
-                                (0.1, 0.1),
-                                (
-                                    2.0 + transform.velocity.0.abs(),
-                                    8.0 + transform.velocity.0.abs(),
-                                ),
-                                1.0 * transform.velocity.0.abs(),
-                                1.0,
-                            );
-                            play_audio!(sink_sfx, "assets/sfx/ball_goal.wav", 1.0, 1.0);
-                            world.despawn(_id).unwrap();
-                            break;
+                        Sound::BallHitPaddle => {
+                            play_audio!(sink_sfx, "assets/sfx/ball_hit_paddle.wav", volume, speed);
                         }
-                        if transform.position.1 < 0.0 || transform.position.1 > screen_height() {
-                            transform.velocity.1 = transform.velocity.1 * -1.0;
-                            transform.position = (
-                                transform.position.0,
-                                transform.position.1.clamp(0.0, screen_height()),
-                            );
+                        Sound::BallHitSide => {
+                            play_audio!(sink_sfx, "assets/sfx/ball_hit_side.wav", volume, speed);
+                        }
+                        Sound::BulletHitPaddle => {
                             play_audio!(
                                 sink_sfx,
-                                "assets/sfx/ball_hit_side.wav",
-                                0.1,
-                                rand::RandomRange::gen_range(0.8, 1.0)
+                                "assets/sfx/bullet_hit_paddle.wav",
+                                volume,
+                                speed
                             );
                         }
-
-                        // Now checking against paddles.
-                        for (_id, paddle_transform, bounds) in &entities {
-                            if test_sphere_capsule((transform, ball), (paddle_transform, bounds)) {
-                                ball.speed = ball.speed + (0.5 / ball.speed);
-                                transform.velocity = (
-                                    (transform.position.0 - paddle_transform.position.0) / bounds.0
-                                        + (paddle_transform.velocity.0 * 0.25),
-                                    (transform.position.1 - paddle_transform.position.1) / bounds.1
-                                        + (paddle_transform.velocity.1 * 0.25),
-                                );
-                                let magnitude = (transform.velocity.0.powf(2.0)
-                                    + transform.velocity.1.powf(2.0))
-                                .sqrt();
-                                transform.velocity = (
-                                    (transform.velocity.0 / magnitude) * ball.speed,
-                                    (transform.velocity.1 / magnitude) * ball.speed,
-                                );
-                                particles.create_particle(
-                                    transform.velocity.0.abs() as i32,
-                                    transform.position,
-                                    (transform.velocity.0 * 2.0, transform.velocity.1 * 2.0),
-                                    4.0 * transform.velocity.0.abs(),
-                                    WHITE,
-                                    0.3,
-                                    (0.1, 0.1),
-                                    (
-                                        2.0 + transform.velocity.0.abs(),
-                                        4.0 + transform.velocity.0.abs(),
-                                    ),
-                                    0.25 * transform.velocity.0.abs(),
-                                    0.25,
-                                );
-                                play_audio!(
-                                    sink_sfx,
-                                    "assets/sfx/ball_hit_paddle.wav",
-                                    0.15,
-                                    rand::RandomRange::gen_range(0.8, 1.0)
-                                );
-                                game_state.hitstun += (ball.speed * 2.0) as i32;
-                            }
+                        Sound::BulletShot => {
+                            play_audio!(sink_sfx, "assets/sfx/bullet_shot.wav", volume, speed);
                         }
-
-                        // And updating our values.
-                        game_state.intensity += ball.speed;
-
-                        // Oh and our particles.
-                        particles.create_particle(
-                            1,
-                            transform.position,
-                            (0.0, 0.0),
-                            16.0,
-                            BLACK,
-                            (game_state.intensity / 4.0) as f64,
-                            (0.0, 0.0),
-                            (0.2, 0.2),
-                            0.0,
-                            0.0,
-                        );
                     }
-                    game_state.intensity *= 4.0;
                 }
             }
+        }
+
+        // Every round counts towards the match, which gets wrapped up (and saved) once it's won.
+        let match_winner = if last_phase == Phase::Ongoing
+            && matches!(game_state.phase, Phase::LeftWin | Phase::RightWin)
+        {
+            game_state.record_round()
         } else {
-            game_state.hitstun -= 1;
+            None
+        };
+        if let Some(winner) = match_winner {
+            match_stats.finish(winner, game_state.knocked_out);
+            events.push(GameEvent::new(Trigger::Win, winner));
+            // A good enough survival run gets to go up on the board.
+            if game_state.mode == GameMode::Survival && !test_game {
+                let entry = LeaderboardEntry {
+                    initials: String::new(),
+                    returns: survival_score(&world, &match_stats),
+                    survived: match_stats.duration,
+                    date: format_date(unix_time()),
+                    seed: survival_seed,
+                    settings: format!(
+                        "{}, {}, {}",
+                        level.name.replace('\t', " "),
+                        game_state.knockout.label(),
+                        if game_state.horizontal_movement {
+                            "free move"
+                        } else {
+                            "fixed"
+                        }
+                    ),
+                };
+                last_entry = None;
+                if leaderboard.qualifies(&entry) {
+                    initials = Some(InitialsEntry::new(entry));
+                }
+            }
+            // Chalking it up to the player's record too. (Test games from the editor don't count.)
+            if !test_game {
+                if let Err(error) = match_stats.export(Path::new(STATS_DIRECTORY)) {
+                    eprintln!("Couldn't export the match stats. ({})", error);
+                }
+                let player_sides = player_sides(&world);
+                if !player_sides.is_empty() {
+                    profile.record_game(player_sides.contains(&winner));
+                    profile.save_or_warn(Path::new(PROFILE_FILE));
+                }
+            }
+        }
+
+        // Seeing if anything the player did this frame earned them something.
+        if !events.is_empty() && !test_game {
+            let player_sides = player_sides(&world);
+            let mut unlocked = false;
+            for event in events
+                .iter()
+                .filter(|event| player_sides.contains(&event.side))
+            {
+                unlocked |= achievements.check(event, &match_stats, &mut profile, real_time);
+            }
+            if unlocked {
+                profile.save_or_warn(Path::new(PROFILE_FILE));
+            }
         }
+        last_phase = game_state.phase;
 
-        if frame_count % 8 == 0 {
-            particles.create_particle(
-                1,
-                (screen_width() / 2.0, -4.0),
-                (0.0, 0.4),
-                2.0,
-                WHITE,
-                60.0,
-                (screen_width() / 2.0, 0.0),
-                (0.0, 0.2),
-                0.0,
-                0.0,
-            );
+        if frame_count.is_multiple_of(8) {
+            let star = particles
+                .preset("ambient_stars")
+                .spread(screen_width() / 2.0, 0.0);
+            particles.emit(&star, (screen_width() / 2.0, -4.0), (0.0, 0.0));
         }
 
+        post_processing.finish(camera.shake());
         next_frame().await
     }
 }
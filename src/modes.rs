@@ -0,0 +1,183 @@
+use crate::Phase;
+
+// Which flavour of game we're playing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Multiball,
+    Survival, // One player holding out against an AI that keeps getting tougher.
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Classic, GameMode::Multiball, GameMode::Survival];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Multiball => "Multiball",
+            GameMode::Survival => "Survival",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Classic => GameMode::Multiball,
+            GameMode::Multiball => GameMode::Survival,
+            GameMode::Survival => GameMode::Classic,
+        }
+    }
+}
+
+// How a multiball round gets decided.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RoundEnd {
+    Goals(i32),     // First side to score this many in the rally.
+    LastBall,       // Keep going until every ball has been scored.
+    TimeLimit(f64), // Whoever's ahead when the clock runs out. (Sudden death on a tie.)
+}
+
+impl RoundEnd {
+    pub const PRESETS: [RoundEnd; 3] = [
+        RoundEnd::Goals(3),
+        RoundEnd::LastBall,
+        RoundEnd::TimeLimit(60.0),
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            RoundEnd::Goals(goals) => format!("first to {}", goals),
+            RoundEnd::LastBall => "last ball".to_string(),
+            RoundEnd::TimeLimit(seconds) => format!("{}s", seconds),
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let index = Self::PRESETS
+            .iter()
+            .position(|preset| preset == self)
+            .unwrap_or(0);
+        Self::PRESETS[(index + 1) % Self::PRESETS.len()]
+    }
+}
+
+// Everything tweakable about multiball.
+#[derive(Clone, Copy, Debug)]
+pub struct MultiballRules {
+    pub max_balls: usize,
+    pub spawn_interval: f64, // Seconds between extra balls showing up.
+    pub hits_per_ball: i32,  // Paddle hits before another ball joins in. (0 turns this off.)
+    pub round_end: RoundEnd,
+}
+
+impl Default for MultiballRules {
+    fn default() -> Self {
+        MultiballRules {
+            max_balls: 4,
+            spawn_interval: 10.0,
+            hits_per_ball: 6,
+            round_end: RoundEnd::Goals(3),
+        }
+    }
+}
+
+// The running tally for the current rally.
+#[derive(Default, Clone, Copy)]
+pub struct RoundState {
+    pub started_at: f64,
+    pub next_ball: f64,
+    pub paddle_hits: i32,
+    pub left_goals: i32,
+    pub right_goals: i32,
+    pub last_scorer: Option<Phase>,
+}
+
+impl RoundState {
+    pub fn new(current_time: f64, rules: &MultiballRules) -> Self {
+        RoundState {
+            started_at: current_time,
+            next_ball: current_time + rules.spawn_interval,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_goal(&mut self, winner: Phase) {
+        match winner {
+            Phase::LeftWin => self.left_goals += 1,
+            Phase::RightWin => self.right_goals += 1,
+            _ => return,
+        }
+        self.last_scorer = Some(winner);
+    }
+
+    // Whoever's scored more this round, if anybody.
+    fn leader(&self) -> Option<Phase> {
+        if self.left_goals > self.right_goals {
+            Some(Phase::LeftWin)
+        } else if self.right_goals > self.left_goals {
+            Some(Phase::RightWin)
+        } else {
+            None
+        }
+    }
+
+    // Works out if the round is over, and who took it.
+    pub fn result(
+        &self,
+        mode: GameMode,
+        rules: &MultiballRules,
+        balls_left: usize,
+        current_time: f64,
+    ) -> Option<Phase> {
+        match mode {
+            GameMode::Classic => self.last_scorer,
+            // Only a goal against whoever's playing ends a survival run, which main works out.
+            GameMode::Survival => None,
+            GameMode::Multiball => match rules.round_end {
+                RoundEnd::Goals(goals) => {
+                    if self.left_goals >= goals || self.right_goals >= goals {
+                        self.leader()
+                    } else {
+                        None
+                    }
+                }
+                RoundEnd::LastBall => {
+                    if balls_left == 0 {
+                        self.leader().or(self.last_scorer)
+                    } else {
+                        None
+                    }
+                }
+                RoundEnd::TimeLimit(seconds) => {
+                    if current_time - self.started_at > seconds {
+                        self.leader()
+                    } else {
+                        None
+                    }
+                }
+            },
+        }
+    }
+
+    // Whether another ball should join in. Resets whatever triggered it.
+    pub fn wants_ball(
+        &mut self,
+        rules: &MultiballRules,
+        balls_left: usize,
+        current_time: f64,
+    ) -> bool {
+        if balls_left >= rules.max_balls {
+            return false;
+        }
+        // Nothing left in play, so there's no waiting around for the next one.
+        if balls_left == 0 || current_time > self.next_ball {
+            self.next_ball = current_time + rules.spawn_interval;
+            return true;
+        }
+        if rules.hits_per_ball > 0 && self.paddle_hits >= rules.hits_per_ball {
+            self.paddle_hits = 0;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,290 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::arena::{arena_height, arena_width};
+use crate::collision::*;
+use crate::level::*;
+use crate::spin::renormalise;
+use crate::theme::Theme;
+use crate::{Ball, Bullet, ParticleStorage, Transform};
+
+// How much of a kick bumpers give the ball.
+const BUMPER_BOOST: f32 = 0.25;
+
+// What an obstacle does when something runs into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Obstacle {
+    Bumper,
+    Wall,
+    Block { hits: i32 },
+    Portal { exit: (f32, f32) },
+}
+
+// A component for anything solid in the arena.
+#[derive(Clone, Copy, Debug)]
+pub struct Collider(pub Shape);
+
+// A component for obstacles sliding back and forth.
+#[derive(Clone, Copy, Debug)]
+pub struct Mover {
+    pub origin: (f32, f32),
+    pub offset: (f32, f32),
+    pub period: f32,
+}
+
+// Going from level coordinates (fractions of the screen) to pixels.
+pub fn to_screen(point: (f32, f32)) -> (f32, f32) {
+    (point.0 * arena_width(), point.1 * arena_height())
+}
+
+// Turning a level's obstacle into its position, collision shape and behaviour.
+pub fn build_obstacle(def: &ObstacleDef) -> ((f32, f32), Shape, Obstacle) {
+    // Walls and blocks are capsules running between their two ends. (Or just a line, if they're
+    // infinitely thin.)
+    let span = |from: (f32, f32), to: (f32, f32), thickness: f32| {
+        let (from, to) = (to_screen(from), to_screen(to));
+        let half = ((to.0 - from.0) / 2.0, (to.1 - from.1) / 2.0);
+        (
+            ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0),
+            if thickness > 0.0 {
+                Shape::Capsule {
+                    half,
+                    radius: thickness / 2.0,
+                }
+            } else {
+                Shape::Segment { half }
+            },
+        )
+    };
+    match *def {
+        ObstacleDef::Bumper { at, radius } => {
+            (to_screen(at), Shape::Circle { radius }, Obstacle::Bumper)
+        }
+        ObstacleDef::Wall {
+            from,
+            to,
+            thickness,
+        } => {
+            let (position, shape) = span(from, to, thickness);
+            (position, shape, Obstacle::Wall)
+        }
+        ObstacleDef::Block {
+            from,
+            to,
+            thickness,
+            hits,
+        } => {
+            let (position, shape) = span(from, to, thickness);
+            (position, shape, Obstacle::Block { hits })
+        }
+        ObstacleDef::Portal { at, exit, radius } => (
+            to_screen(at),
+            Shape::Circle { radius },
+            Obstacle::Portal {
+                exit: to_screen(exit),
+            },
+        ),
+    }
+}
+
+// Putting a level's obstacles into the world.
+pub fn spawn_obstacles(world: &mut World, level: &Level) {
+    for placed in &level.obstacles {
+        let (position, shape, obstacle) = build_obstacle(&placed.def);
+        let entity = world.spawn((
+            Transform {
+                position,
+                velocity: (0.0, 0.0),
+            },
+            Collider(shape),
+            obstacle,
+        ));
+        if let Some(motion) = placed.motion {
+            world
+                .insert_one(
+                    entity,
+                    Mover {
+                        origin: position,
+                        offset: to_screen(motion.offset),
+                        period: motion.period,
+                    },
+                )
+                .unwrap();
+        }
+    }
+}
+
+// Sliding the moving obstacles along.
+pub fn update_movers(world: &mut World, current_time: f64) {
+    for (_id, (transform, mover)) in world.query_mut::<(&mut Transform, &Mover)>() {
+        let phase = ((current_time / mover.period as f64) * std::f64::consts::TAU).sin() as f32;
+        transform.position = (
+            mover.origin.0 + mover.offset.0 * phase,
+            mover.origin.1 + mover.offset.1 * phase,
+        );
+    }
+}
+
+// Where something comes out of a portal, pushed clear so it doesn't fall straight back in.
+fn portal_exit(exit: (f32, f32), shape: &Shape, transform: &Transform, radius: f32) -> (f32, f32) {
+    let speed = (transform.velocity.0.powf(2.0) + transform.velocity.1.powf(2.0)).sqrt();
+    let direction = if speed > 0.0 {
+        (transform.velocity.0 / speed, transform.velocity.1 / speed)
+    } else {
+        (1.0, 0.0)
+    };
+    let clearance = shape.radius() + radius + 2.0;
+    (
+        exit.0 + direction.0 * clearance,
+        exit.1 + direction.1 * clearance,
+    )
+}
+
+fn obstacle_list(world: &World) -> Vec<(Entity, Transform, Collider, Obstacle)> {
+    world
+        .query::<(&Transform, &Collider, &Obstacle)>()
+        .iter()
+        .map(|(e, (&t, &c, &o))| (e, t, c, o)) // Copy out of the world
+        .collect::<Vec<_>>()
+}
+
+// Knocking a hit off a block, and clearing it away once it's done for.
+fn damage_blocks(world: &mut World, particles: &mut ParticleStorage, damaged: Vec<Entity>) {
+    for block in damaged {
+        let broken = match world.query_one_mut::<(&Transform, &mut Obstacle)>(block) {
+            Ok((transform, Obstacle::Block { hits })) => {
+                *hits -= 1;
+                (*hits <= 0).then_some(transform.position)
+            }
+            _ => None,
+        };
+        if let Some(position) = broken {
+            particles.emit(&particles.preset("block_break"), position, (0.0, 0.0));
+            world.despawn(block).unwrap();
+        }
+    }
+}
+
+// Bouncing balls off (or through) everything. Returns how many hits there were.
+pub fn collide_balls(world: &mut World, particles: &mut ParticleStorage) -> i32 {
+    let obstacles = obstacle_list(world);
+    let mut hits = 0;
+    let mut damaged: Vec<Entity> = Vec::new();
+    for (_id, (transform, ball)) in world.query_mut::<(&mut Transform, &mut Ball)>() {
+        for (obstacle_id, obstacle_transform, collider, obstacle) in &obstacles {
+            let Some(contact) = circle_contact(
+                &collider.0,
+                obstacle_transform.position,
+                transform.position,
+                ball.radius,
+            ) else {
+                continue;
+            };
+            hits += 1;
+            if let Obstacle::Portal { exit } = obstacle {
+                transform.position = portal_exit(*exit, &collider.0, transform, ball.radius);
+                continue;
+            }
+            // Pushing the ball back out, and reflecting it if it was heading in.
+            transform.position = (
+                transform.position.0 + contact.normal.0 * contact.depth,
+                transform.position.1 + contact.normal.1 * contact.depth,
+            );
+            let along =
+                transform.velocity.0 * contact.normal.0 + transform.velocity.1 * contact.normal.1;
+            if along < 0.0 {
+                transform.velocity = (
+                    transform.velocity.0 - 2.0 * along * contact.normal.0,
+                    transform.velocity.1 - 2.0 * along * contact.normal.1,
+                );
+            }
+            match obstacle {
+                Obstacle::Bumper => {
+                    ball.speed += BUMPER_BOOST / ball.speed;
+                    renormalise(transform, ball.speed);
+                }
+                Obstacle::Block { .. } => damaged.push(*obstacle_id),
+                _ => {}
+            }
+            let spark = particles.preset("impact_spark").count(4);
+            particles.emit(&spark, transform.position, contact.normal);
+        }
+    }
+    damage_blocks(world, particles, damaged);
+    hits
+}
+
+// Bullets stop at walls, chip at blocks and go through portals. Returns how many hit.
+pub fn collide_bullets(world: &mut World, particles: &mut ParticleStorage) -> i32 {
+    let obstacles = obstacle_list(world);
+    let mut hits = 0;
+    let mut damaged: Vec<Entity> = Vec::new();
+    let mut despawn_queue: Vec<Entity> = Vec::new();
+    for (bullet_id, (transform, bullet)) in world.query_mut::<(&mut Transform, &Bullet)>() {
+        for (obstacle_id, obstacle_transform, collider, obstacle) in &obstacles {
+            if circle_contact(
+                &collider.0,
+                obstacle_transform.position,
+                transform.position,
+                bullet.radius,
+            )
+            .is_none()
+            {
+                continue;
+            }
+            if let Obstacle::Portal { exit } = obstacle {
+                transform.position = portal_exit(*exit, &collider.0, transform, bullet.radius);
+                continue;
+            }
+            if let Obstacle::Block { .. } = obstacle {
+                damaged.push(*obstacle_id);
+            }
+            hits += 1;
+            despawn_queue.push(bullet_id);
+            particles.emit(
+                &particles.preset("impact_spark"),
+                transform.position,
+                (-transform.velocity.0, -transform.velocity.1),
+            );
+            break;
+        }
+    }
+    for scrap in despawn_queue {
+        world.despawn(scrap).unwrap();
+    }
+    damage_blocks(world, particles, damaged);
+    hits
+}
+
+pub fn draw_obstacle(position: (f32, f32), shape: &Shape, obstacle: &Obstacle, theme: &Theme) {
+    let (a, b) = shape.endpoints(position);
+    match obstacle {
+        Obstacle::Bumper => {
+            draw_circle(a.0, a.1, shape.radius(), theme.shadow);
+            draw_circle_lines(a.0, a.1, shape.radius(), 4.0, theme.foreground);
+            draw_circle_lines(a.0, a.1, shape.radius() / 2.0, 2.0, theme.foreground);
+        }
+        Obstacle::Wall | Obstacle::Block { .. } => {
+            let color = match obstacle {
+                Obstacle::Block { hits } if *hits <= 1 => theme.danger,
+                Obstacle::Block { .. } => theme.hazard,
+                _ => theme.foreground,
+            };
+            draw_line(a.0, a.1, b.0, b.1, (shape.radius() * 2.0).max(2.0), color);
+            draw_circle(a.0, a.1, shape.radius(), color);
+            draw_circle(b.0, b.1, shape.radius(), color);
+        }
+        Obstacle::Portal { exit } => {
+            draw_circle_lines(a.0, a.1, shape.radius(), 3.0, theme.portal);
+            draw_circle_lines(exit.0, exit.1, shape.radius() / 2.0, 2.0, theme.portal);
+        }
+    }
+}
+
+pub fn draw_obstacles(world: &mut World, theme: &Theme) {
+    for (_id, (transform, collider, obstacle)) in
+        world.query_mut::<(&Transform, &Collider, &Obstacle)>()
+    {
+        draw_obstacle(transform.position, &collider.0, obstacle, theme);
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation};
+use macroquad::models::{Mesh, Vertex};
+use macroquad::prelude::*;
+
+use crate::emitters::Emitter;
+
+// How many particles can be alive at once. Cycled from the menu.
+pub const PARTICLE_BUDGETS: [usize; 4] = [512, 2048, 4096, 8192];
+pub const DEFAULT_PARTICLE_BUDGET: usize = 4096;
+
+// Macroquad clamps a single draw call to 5000 indices, so quads go out in batches under that.
+const QUADS_PER_BATCH: usize = 800;
+
+// The particle texture is a soft dot on the left and a solid square on the right.
+const DOT_SIZE: u16 = 32;
+
+// How long a streak gets, in seconds' worth of its velocity.
+const STREAK_LENGTH: f32 = 0.03;
+
+// The most colours a gradient can run through.
+pub const MAX_STOPS: usize = 4;
+
+// Same as macroquad's own vertex shader, for materials that only change the fragments.
+pub const VERTEX_SHADER: &str = "#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+varying lowp vec2 uv;
+varying lowp vec4 color;
+uniform mat4 Model;
+uniform mat4 Projection;
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+";
+const FRAGMENT_SHADER: &str = "#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+uniform sampler2D Texture;
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+";
+
+// How a particle mixes with whatever's under it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Blend {
+    #[default]
+    Alpha,
+    Additive, // Brightening, for sparks and glows.
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ParticleShape {
+    #[default]
+    Dot,
+    Square,
+    Streak, // A line stretched out along the particle's velocity.
+}
+
+// Colours a particle passes through over its life, at points from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gradient {
+    stops: [(f32, Color); MAX_STOPS],
+    count: usize,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::solid(WHITE)
+    }
+}
+
+impl Gradient {
+    pub fn solid(color: Color) -> Self {
+        Gradient {
+            stops: [(0.0, color); MAX_STOPS],
+            count: 1,
+        }
+    }
+
+    // Adding another colour further along. Stops past the limit are ignored.
+    pub fn stop(mut self, at: f32, color: Color) -> Self {
+        if self.count < MAX_STOPS {
+            self.stops[self.count] = (at.clamp(0.0, 1.0), color);
+            self.count += 1;
+            self.stops[..self.count].sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        self
+    }
+
+    // Multiplying every colour, for reusing one gradient in a different hue.
+    pub fn tint(mut self, tint: Color) -> Self {
+        for (_at, color) in &mut self.stops[..self.count] {
+            *color = Color::new(
+                color.r * tint.r,
+                color.g * tint.g,
+                color.b * tint.b,
+                color.a * tint.a,
+            );
+        }
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> Color {
+        let stops = &self.stops[..self.count];
+        match stops.iter().position(|&(at, _)| at > t) {
+            None => stops[stops.len() - 1].1,
+            Some(0) => stops[0].1,
+            Some(index) => {
+                let ((from_at, from), (to_at, to)) = (stops[index - 1], stops[index]);
+                let f = (t - from_at) / (to_at - from_at).max(f32::EPSILON);
+                Color::new(
+                    from.r + (to.r - from.r) * f,
+                    from.g + (to.g - from.g) * f,
+                    from.b + (to.b - from.b) * f,
+                    from.a + (to.a - from.a) * f,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct Particle {
+    pub position: (f32, f32),
+    pub velocity: (f32, f32), // Pixels per second.
+    pub size: f32,
+    pub end_size: f32, // What the size shrinks (or grows) to by the end of its life.
+    pub gradient: Gradient,
+    pub fade: f32,    // The last fraction of its life spent fading out.
+    pub gravity: f32, // Pixels per second, per second. Downwards is positive.
+    pub drag: f32,    // How much of its velocity it loses each second.
+    pub blend: Blend,
+    pub shape: ParticleShape,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+impl Particle {
+    // How far through its life the particle is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+
+    pub fn current_size(&self) -> f32 {
+        (self.size + (self.end_size - self.size) * self.progress()).max(0.0)
+    }
+
+    pub fn current_color(&self) -> Color {
+        let t = self.progress();
+        let mut color = self.gradient.sample(t);
+        if self.fade > 0.0 {
+            color.a *= ((1.0 - t) / self.fade).clamp(0.0, 1.0);
+        }
+        color
+    }
+
+    // The four corners of the particle's quad, along with where they sit on the texture.
+    fn corners(&self) -> [(f32, f32, f32, f32); 4] {
+        let radius = self.current_size();
+        let (x, y) = self.position;
+        let (u0, u1) = match self.shape {
+            ParticleShape::Dot => (0.0, 0.5),
+            _ => (0.52, 0.98), // Staying clear of the dot's soft edge.
+        };
+        if self.shape != ParticleShape::Streak {
+            return [
+                (x - radius, y - radius, u0, 0.0),
+                (x + radius, y - radius, u1, 0.0),
+                (x + radius, y + radius, u1, 1.0),
+                (x - radius, y + radius, u0, 1.0),
+            ];
+        }
+        // Streaks trail back from the particle along where it came from.
+        let speed = (self.velocity.0.powf(2.0) + self.velocity.1.powf(2.0)).sqrt();
+        let (along, across) = if speed > 0.0 {
+            let along = (self.velocity.0 / speed, self.velocity.1 / speed);
+            (along, (-along.1, along.0))
+        } else {
+            ((1.0, 0.0), (0.0, 1.0))
+        };
+        let length = radius + speed * STREAK_LENGTH;
+        let width = (radius / 2.0).max(0.5);
+        let tail = (x - along.0 * length, y - along.1 * length);
+        [
+            (
+                tail.0 - across.0 * width,
+                tail.1 - across.1 * width,
+                u0,
+                0.0,
+            ),
+            (x - across.0 * width, y - across.1 * width, u1, 0.0),
+            (x + across.0 * width, y + across.1 * width, u1, 1.0),
+            (
+                tail.0 + across.0 * width,
+                tail.1 + across.1 * width,
+                u0,
+                1.0,
+            ),
+        ]
+    }
+}
+
+// A fixed-size pool of particles. The live ones are packed at the front, so dying is just a swap.
+pub struct ParticleStorage {
+    particles: Vec<Particle>,
+    alive: usize,
+    mesh: Mesh,
+    additive: Option<Material>, // None if it couldn't be built.
+    prepared: bool,
+    presets: HashMap<String, Emitter>,
+    density: f32, // How many of each emitter's particles actually get made.
+}
+
+impl ParticleStorage {
+    pub fn new(budget: usize, presets: HashMap<String, Emitter>) -> Self {
+        Self {
+            particles: vec![Particle::default(); budget],
+            alive: 0,
+            mesh: Mesh {
+                vertices: Vec::with_capacity(QUADS_PER_BATCH * 4),
+                indices: Vec::with_capacity(QUADS_PER_BATCH * 6),
+                texture: None,
+            },
+            additive: None,
+            prepared: false,
+            presets,
+            density: 1.0,
+        }
+    }
+
+    // A copy of one of the named emitters, ready to be tweaked and emitted.
+    pub fn preset(&self, name: &str) -> Emitter {
+        self.presets.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn emit(&mut self, emitter: &Emitter, position: (f32, f32), velocity: (f32, f32)) {
+        emitter.emit(self, position, velocity);
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        self.density = density.clamp(0.0, 1.0);
+    }
+
+    pub fn alive(&self) -> usize {
+        self.alive
+    }
+
+    pub fn budget(&self) -> usize {
+        self.particles.len()
+    }
+
+    // Changing the pool size, dropping whatever doesn't fit anymore.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.particles.resize(budget, Particle::default());
+        self.alive = self.alive.min(budget);
+    }
+
+    // Adding a particle if there's room for it. Once the budget's spent new ones are just dropped.
+    pub fn spawn(&mut self, particle: Particle) -> bool {
+        if self.alive >= self.particles.len() {
+            return false;
+        }
+        self.particles[self.alive] = particle;
+        self.alive += 1;
+        true
+    }
+
+    // Moving everything along by `dt` seconds and clearing out the dead.
+    pub fn update(&mut self, dt: f32) {
+        let mut index = 0;
+        while index < self.alive {
+            let particle = &mut self.particles[index];
+            particle.age += dt;
+            if particle.age >= particle.lifetime {
+                self.alive -= 1;
+                self.particles.swap(index, self.alive);
+                continue;
+            }
+            let friction = (1.0 - particle.drag * dt).max(0.0);
+            particle.velocity = (
+                particle.velocity.0 * friction,
+                (particle.velocity.1 + particle.gravity * dt) * friction,
+            );
+            particle.position = (
+                particle.position.0 + particle.velocity.0 * dt,
+                particle.position.1 + particle.velocity.1 * dt,
+            );
+            index += 1;
+        }
+    }
+
+    // Building the texture and additive material on the first draw, once there's a window.
+    fn prepare(&mut self) {
+        self.prepared = true;
+        self.mesh.texture = Some(particle_texture());
+        self.additive = load_material(
+            ShaderSource::Glsl {
+                vertex: VERTEX_SHADER,
+                fragment: FRAGMENT_SHADER,
+            },
+            MaterialParams {
+                pipeline_params: PipelineParams {
+                    color_blend: Some(BlendState::new(
+                        Equation::Add,
+                        BlendFactor::Value(BlendValue::SourceAlpha),
+                        BlendFactor::One,
+                    )),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .map_err(|error| eprintln!("No additive particles, blending normally. ({:?})", error))
+        .ok();
+    }
+
+    fn flush(&mut self) {
+        if !self.mesh.indices.is_empty() {
+            draw_mesh(&self.mesh);
+        }
+        self.mesh.vertices.clear();
+        self.mesh.indices.clear();
+    }
+
+    // Drawing every live particle as a textured quad, batched up by blend mode.
+    pub fn draw(&mut self) {
+        if !self.prepared {
+            self.prepare();
+        }
+        for blend in [Blend::Alpha, Blend::Additive] {
+            if let (Blend::Additive, Some(material)) = (blend, &self.additive) {
+                gl_use_material(material);
+            }
+            for index in 0..self.alive {
+                let particle = self.particles[index];
+                if particle.blend != blend || particle.current_size() <= 0.0 {
+                    continue;
+                }
+                let color = particle.current_color();
+                let base = self.mesh.vertices.len() as u16;
+                for (x, y, u, v) in particle.corners() {
+                    self.mesh.vertices.push(Vertex::new(x, y, 0.0, u, v, color));
+                }
+                self.mesh
+                    .indices
+                    .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+                if self.mesh.indices.len() >= QUADS_PER_BATCH * 6 {
+                    self.flush();
+                }
+            }
+            self.flush();
+        }
+        gl_use_default_material();
+    }
+}
+
+// A white circle with a slightly soft edge, beside a solid white square.
+fn particle_texture() -> Texture2D {
+    let half = DOT_SIZE as f32 / 2.0;
+    let mut bytes = Vec::with_capacity(DOT_SIZE as usize * DOT_SIZE as usize * 8);
+    for y in 0..DOT_SIZE {
+        for x in 0..DOT_SIZE * 2 {
+            let alpha = if x < DOT_SIZE {
+                let distance =
+                    ((x as f32 + 0.5 - half).powf(2.0) + (y as f32 + 0.5 - half).powf(2.0)).sqrt();
+                (half - distance).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            bytes.extend([255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+    let texture = Texture2D::from_rgba8(DOT_SIZE * 2, DOT_SIZE, &bytes);
+    texture.set_filter(FilterMode::Linear);
+    texture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A pool with just the one particle in it, straight from `emitter`.
+    fn emit_one(emitter: Emitter) -> ParticleStorage {
+        let mut storage = ParticleStorage::new(4, HashMap::new());
+        storage.emit(&emitter.count(1), (10.0, 20.0), (0.0, 0.0));
+        assert_eq!(storage.alive(), 1);
+        storage
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn emitted_particles_keep_the_emitters_settings() {
+        let storage = emit_one(
+            Emitter::new()
+                .gravity(50.0)
+                .drag(0.5)
+                .color(RED)
+                .stop(1.0, BLUE),
+        );
+        let particle = storage.particles[0];
+        assert_eq!(particle.gravity, 50.0);
+        assert_eq!(particle.drag, 0.5);
+        assert_eq!(particle.gradient, Gradient::solid(RED).stop(1.0, BLUE));
+    }
+
+    #[test]
+    fn colour_changes_over_its_life() {
+        let mut storage = emit_one(Emitter::new().lifetime(1.0, 0.0).color(RED).stop(1.0, BLUE));
+        assert_eq!(storage.particles[0].current_color(), RED);
+        storage.update(0.5);
+        let halfway = storage.particles[0].current_color();
+        assert!(close(halfway.r, (RED.r + BLUE.r) / 2.0));
+        assert!(close(halfway.b, (RED.b + BLUE.b) / 2.0));
+        storage.update(0.49);
+        let end = storage.particles[0].current_color();
+        assert!((end.b - BLUE.b).abs() < 0.02);
+        assert!((end.r - BLUE.r).abs() < 0.02);
+    }
+
+    #[test]
+    fn gravity_and_drag_change_its_velocity() {
+        let mut storage = emit_one(
+            Emitter::new()
+                .lifetime(1.0, 0.0)
+                .velocity(100.0, 0.0)
+                .gravity(50.0)
+                .drag(0.5),
+        );
+        storage.update(0.1);
+        let particle = storage.particles[0];
+        // Pulled down by gravity, then slowed by 5% of drag.
+        assert!(close(particle.velocity.0, 95.0));
+        assert!(close(particle.velocity.1, 4.75));
+        assert!(close(particle.position.0, 10.0 + 9.5));
+        assert!(close(particle.position.1, 20.0 + 0.475));
+    }
+
+    #[test]
+    fn dies_at_the_end_of_its_life() {
+        let mut storage = emit_one(Emitter::new().lifetime(0.5, 0.0));
+        storage.update(0.4);
+        assert_eq!(storage.alive(), 1);
+        storage.update(0.2);
+        assert_eq!(storage.alive(), 0);
+    }
+}
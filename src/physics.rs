@@ -0,0 +1,754 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::*;
+
+// The sound effects the physics can ask for. The game plays them, the simulator doesn't bother.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Sound {
+    BallGoal,
+    BallHitPaddle,
+    BallHitSide,
+    BulletHitPaddle,
+    BulletShot,
+}
+
+// Everything a physics step needs besides the world and the game state, and what it hands back.
+pub struct Frame<'a> {
+    pub current_time: f64,
+    pub delta: f32, // Seconds since the last frame.
+    pub level: &'a Level,
+    pub theme: &'a Theme, // For tinting the goal bursts.
+    pub particles: &'a mut ParticleStorage,
+    pub camera: &'a mut GameCamera,
+    pub match_stats: &'a mut MatchStats,
+    pub trail_tint: Option<Color>, // For the ball's trail.
+    pub typing: bool,              // Keys are going to the console, so nobody steers.
+    pub events: Vec<GameEvent>,
+    pub sounds: Vec<(Sound, f32, f32)>, // (Sound, volume, speed.)
+}
+
+// The ball an AI paddle goes after (whichever's closest), and how far off it is. (Squared.)
+pub fn ai_target(
+    balls: &[(Entity, Transform, Ball)],
+    position: (f32, f32),
+) -> Option<((Entity, Transform, Ball), f32)> {
+    let (mut target, mut target_distance) = (*balls.first()?, f32::MAX);
+    for (id, ball_transform, ball_ball) in balls {
+        let temp_distance = square_distance(
+            position.0,
+            position.1,
+            ball_transform.position.0,
+            ball_transform.position.1,
+        );
+        if temp_distance < target_distance {
+            target = (*id, *ball_transform, *ball_ball); // Setting the current target.
+            target_distance = temp_distance;
+        }
+    }
+    Some((target, target_distance))
+}
+
+// Serving the first ball and getting everything ready for a new round.
+pub fn start_round(world: &mut World, game_state: &mut GameState, current_time: f64) {
+    // Our ball.
+    spawn_ball(
+        world,
+        (
+            ((game_state.phase != Phase::RightWin) as i32 as f32)
+                - ((game_state.phase == Phase::RightWin) as i32 as f32),
+            0.0,
+        ),
+    );
+    // Resetting the bounds of the paddles.
+    for (_id, (health, bounds)) in world.query_mut::<(&mut PaddleHealth, &mut Bounds)>() {
+        health.restore(bounds);
+    }
+    game_state.knocked_out = false;
+    // And finally, kicking everything off.
+    game_state.phase = Phase::Ongoing;
+    game_state.next_power_up = current_time + POWER_UP_INTERVAL;
+    game_state.round = RoundState::new(current_time, &game_state.rules);
+}
+
+// One frame's worth of the game itself: moving, steering, shooting, bouncing and scoring.
+pub fn step_physics(world: &mut World, game_state: &mut GameState, frame: &mut Frame) {
+    let current_time = frame.current_time;
+    let delta = frame.delta;
+    let level = frame.level;
+    let theme = frame.theme;
+    let trail_tint = frame.trail_tint;
+    let typing = frame.typing;
+    let particles = &mut *frame.particles;
+    let camera = &mut *frame.camera;
+    let match_stats = &mut *frame.match_stats;
+    let events = &mut frame.events;
+    let sounds = &mut frame.sounds;
+
+    // Let's pull a Mario 64.
+    for _i in 1..4 {
+        // Updating positions from velocities.
+        let slowed_sides = slowed_sides(world);
+        for (_id, (transform, ball)) in world.query_mut::<(&mut Transform, Option<&Ball>)>() {
+            let time_scale = match ball {
+                Some(_) => slow_mo_scale(&slowed_sides, transform),
+                None => 1.0,
+            } * game_state.time_scale;
+            transform.position = (
+                clamp(
+                    transform.position.0 + transform.velocity.0 * time_scale,
+                    -16.0,
+                    arena_width() + 16.0,
+                ),
+                clamp(
+                    transform.position.1 + transform.velocity.1 * time_scale,
+                    -16.0,
+                    arena_height() + 16.0,
+                ),
+            );
+        }
+        confine_paddles(world);
+        update_movers(world, current_time);
+
+        // Sweeping up any shots that made it out of the arena, rather than letting them pile up.
+        let strays = world
+            .query::<(&Transform, &Bullet)>()
+            .iter()
+            .filter(|(_e, (transform, _bullet))| {
+                !(0.0..=arena_width()).contains(&transform.position.0)
+                    || !(0.0..=arena_height()).contains(&transform.position.1)
+            })
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        for scrap in strays {
+            world.despawn(scrap).unwrap();
+        }
+
+        // Processing Paddles.
+        {
+            let entities = world
+                .query::<(&Transform, &Ball)>()
+                .iter()
+                .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
+                .collect::<Vec<_>>();
+            let mut spawn_queue: Vec<(Transform, Bullet, Sprite)> = Vec::new();
+            for (id, (transform, control, effects, health, actions, brain)) in world.query_mut::<(
+                &mut Transform,
+                &mut ControlType,
+                Option<&TimedEffects>,
+                Option<&PaddleHealth>,
+                Option<&mut PaddleActions>,
+                Option<&Brain>,
+            )>() {
+                // Reversed controls flip the steering, rapid fire shortens the cooldown.
+                let steering = match effects {
+                    Some(effects) if effects.has(PowerUpKind::Reverse) => -1.0,
+                    _ => 1.0,
+                };
+                let fire_cooldown = match effects {
+                    Some(effects) if effects.has(PowerUpKind::RapidFire) => 0.12,
+                    _ => 0.35,
+                };
+
+                // Slowing things down just a bit, just to ease control. (Scaled like everything
+                // else per step, so slow-mo doesn't change how the paddles handle.)
+                let scale = game_state.time_scale;
+                let damping = 0.95f32.powf(scale);
+                transform.velocity = (
+                    transform.velocity.0 * damping,
+                    transform.velocity.1 * damping,
+                );
+
+                // Knocked out paddles just drift.
+                if health.is_some_and(|health| health.destroyed) {
+                    continue;
+                }
+
+                // Handling Controls
+                match control {
+                    ControlType::Player(x, s) => {
+                        // Nothing's held down while someone's typing.
+                        let down = |key| !typing && is_key_down(key);
+                        let pressed = |key| !typing && is_key_pressed(key);
+                        let vertical = ((down(x.down[0]) as i32 as f32)
+                            - (down(x.up[0]) as i32 as f32))
+                            * steering;
+                        // Sideways movement only counts if the rules allow it.
+                        let horizontal = ((down(x.strafe_right[0]) as i32 as f32)
+                            - (down(x.strafe_left[0]) as i32 as f32))
+                            * steering
+                            * game_state.horizontal_movement as i32 as f32;
+                        transform.velocity = (
+                            transform.velocity.0 + horizontal * 0.3 * scale,
+                            transform.velocity.1 + vertical * 0.3 * scale,
+                        );
+                        if let Some(actions) = actions {
+                            if pressed(x.dash[0])
+                                && actions.try_dash(transform, (horizontal, vertical), current_time)
+                            {
+                                particles.emit(
+                                    &particles.preset("dash_burst"),
+                                    transform.position,
+                                    transform.velocity,
+                                );
+                            }
+                            if pressed(x.parry[0]) && actions.try_parry(current_time) {
+                                sounds.push((
+                                    Sound::BulletShot,
+                                    0.05,
+                                    rand::RandomRange::gen_range(0.5, 0.6),
+                                ));
+                            }
+                        }
+                        if (down(x.right[0]) ^ down(x.left[0])) && current_time > *s {
+                            *s = current_time + fire_cooldown;
+                            match_stats
+                                .side_mut(Side::of(transform.position.0))
+                                .shots_fired += 1;
+                            spawn_queue.push((
+                                Transform {
+                                    position: (
+                                        transform.position.0
+                                            + ((down(x.right[0]) as i32 as f32)
+                                                - (down(x.left[0]) as i32 as f32))
+                                                * 32.0,
+                                        transform.position.1,
+                                    ),
+                                    velocity: (
+                                        (((down(x.right[0]) as i32 as f32)
+                                            - (down(x.left[0]) as i32 as f32))
+                                            * 2.0),
+                                        rand::RandomRange::gen_range(-0.1, 0.1),
+                                    ),
+                                },
+                                Bullet {
+                                    radius: 2.0,
+                                    owner: Some(id),
+                                },
+                                Sprite::new(BULLET_SPRITE).started_at(current_time),
+                            ));
+                            sounds.push((
+                                Sound::BulletShot,
+                                0.05,
+                                rand::RandomRange::gen_range(0.9, 1.0),
+                            ));
+                        }
+                    }
+                    ControlType::AI(s) => {
+                        // In survival the AI steps up as time goes on, and starts shooting back.
+                        let level = if game_state.mode == GameMode::Survival
+                            && game_state.phase == Phase::Ongoing
+                        {
+                            Some(survival_level(current_time - game_state.round.started_at))
+                        } else {
+                            None
+                        };
+                        // Otherwise it plays however its brain says.
+                        let brain = brain.copied().unwrap_or_default();
+                        let reach = 0.25 * brain.tracking * level.map_or(1.0, ai_tracking);
+                        if let Some(cooldown) = level.map_or(brain.fire_cooldown, ai_fire_cooldown)
+                        {
+                            if current_time > *s {
+                                *s = current_time + cooldown;
+                                let facing = (arena_width() / 2.0 - transform.position.0).signum();
+                                match_stats
+                                    .side_mut(Side::of(transform.position.0))
+                                    .shots_fired += 1;
+                                spawn_queue.push((
+                                    Transform {
+                                        position: (
+                                            transform.position.0 + facing * 32.0,
+                                            transform.position.1,
+                                        ),
+                                        velocity: (
+                                            facing * 2.0,
+                                            rand::RandomRange::gen_range(-0.1, 0.1),
+                                        ),
+                                    },
+                                    Bullet {
+                                        radius: 2.0,
+                                        owner: Some(id),
+                                    },
+                                    Sprite::new(BULLET_SPRITE).started_at(current_time),
+                                ));
+                                sounds.push((
+                                    Sound::BulletShot,
+                                    0.05,
+                                    rand::RandomRange::gen_range(0.7, 0.8),
+                                ));
+                            }
+                        }
+                        if let Some((target, target_distance)) =
+                            ai_target(&entities, transform.position)
+                        {
+                            transform.velocity = (
+                                transform.velocity.0,
+                                transform.velocity.1
+                                    + ((((transform.position.1 < target.1.position.1) as i32
+                                        as f32)
+                                        - ((transform.position.1 > target.1.position.1) as i32
+                                            as f32))
+                                        * (60.0 * target_distance.sqrt() / arena_width()))
+                                    .clamp(-reach, reach)
+                                        * steering
+                                        * scale,
+                            )
+                        }
+                    }
+                }
+
+                // Porbatabled.
+                particles.emit(
+                    &particles.preset("paddle_trail"),
+                    transform.position,
+                    (0.0, 0.0),
+                );
+            }
+            world.spawn_batch(spawn_queue);
+        }
+
+        // Power-ups getting picked up.
+        if collect_power_ups(world, particles, current_time) > 0 {
+            sounds.push((
+                Sound::BallHitSide,
+                0.1,
+                rand::RandomRange::gen_range(1.4, 1.6),
+            ));
+        }
+
+        // Obstacles soaking up shots.
+        if collide_bullets(world, particles) > 0 {
+            sounds.push((
+                Sound::BulletHitPaddle,
+                0.03,
+                rand::RandomRange::gen_range(1.2, 1.4),
+            ));
+        }
+
+        // Bullet stuff.
+        {
+            let mut bullet_has_collided: Vec<&Entity> = Vec::new();
+            let mut knocked_out: Vec<Entity> = Vec::new();
+            let mut parried: Vec<(Entity, Entity, f32)> = Vec::new();
+            let paddle_sides = paddle_sides(world);
+            let bullets: Vec<(Entity, Transform, Bullet)> = world
+                .query::<(&Transform, &Bullet)>()
+                .iter()
+                .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
+                .collect::<Vec<_>>();
+            for bullet in &bullets {
+                for (_id, (transform, ball)) in world.query_mut::<(&mut Transform, &mut Ball)>() {
+                    if square_distance(
+                        bullet.1.position.0,
+                        bullet.1.position.1,
+                        transform.position.0,
+                        transform.position.1,
+                    ) < ball.radius.powf(2.0)
+                    {
+                        transform.velocity = (
+                            (transform.position.0 - bullet.1.position.0) / 2.0
+                                + (bullet.1.velocity.0 * 0.25),
+                            (transform.position.1 - bullet.1.position.1) / 2.0
+                                + (bullet.1.velocity.1 * 0.25),
+                        );
+                        let magnitude = (transform.velocity.0.powf(2.0)
+                            + transform.velocity.1.powf(2.0))
+                        .sqrt();
+                        transform.velocity = (
+                            (transform.velocity.0 / magnitude) * ball.speed,
+                            (transform.velocity.1 / magnitude) * ball.speed,
+                        );
+                        spin_from_bullet(ball, transform, &bullet.1);
+                        if let Some(side) = side_of(&paddle_sides, bullet.2.owner) {
+                            match_stats.side_mut(side).shots_hit_ball += 1;
+                        }
+                        let spark = particles.preset("impact_spark").intensity(2.0);
+                        particles.emit(&spark, bullet.1.position, transform.velocity);
+                        bullet_has_collided.push(&bullet.0);
+                        sounds.push((
+                            Sound::BallHitSide,
+                            0.05,
+                            rand::RandomRange::gen_range(0.8, 1.0),
+                        ));
+                        // A bullet only gets to hit one thing.
+                        break;
+                    }
+                }
+                if bullet_has_collided.last() == Some(&&bullet.0) {
+                    continue;
+                }
+                for (id, (transform, bounds, health, actions)) in world.query_mut::<(
+                    &mut Transform,
+                    &mut Bounds,
+                    Option<&mut PaddleHealth>,
+                    Option<&PaddleActions>,
+                )>() {
+                    if health.as_ref().is_some_and(|health| health.destroyed) {
+                        continue;
+                    }
+                    if test_sphere_capsule(
+                        (
+                            &bullet.1,
+                            &Ball {
+                                radius: bullet.2.radius,
+                                speed: 0.0,
+                                ..Default::default()
+                            },
+                        ),
+                        (transform, bounds),
+                    ) {
+                        // A well-timed parry sends it right back.
+                        if let Some(actions) = actions {
+                            if actions.is_parrying(current_time) {
+                                parried.push((bullet.0, id, actions.facing()));
+                                break;
+                            }
+                        }
+                        match health {
+                            Some(health) => {
+                                if health.damage(bounds, 1.0, current_time, game_state.knockout) {
+                                    knocked_out.push(id);
+                                }
+                                if bounds.1 <= health.min_length {
+                                    if let Some(side) = side_of(&paddle_sides, bullet.2.owner) {
+                                        events.push(GameEvent::new(Trigger::Shrink, side));
+                                    }
+                                }
+                            }
+                            None => bounds.1 -= 1.0,
+                        }
+                        if let Some(side) = side_of(&paddle_sides, bullet.2.owner) {
+                            match_stats.side_mut(side).shots_hit_paddle += 1;
+                        }
+                        match_stats
+                            .side_mut(Side::of(transform.position.0))
+                            .damage_taken += 1.0;
+                        let spark = particles.preset("impact_spark").intensity(2.0);
+                        particles.emit(&spark, bullet.1.position, transform.velocity);
+                        bullet_has_collided.push(&bullet.0);
+                        sounds.push((
+                            Sound::BulletHitPaddle,
+                            0.05,
+                            rand::RandomRange::gen_range(0.8, 1.0),
+                        ));
+                        break;
+                    }
+                }
+            }
+            for scrap in bullet_has_collided {
+                let _ = world.despawn(*scrap);
+                camera.hit_stop(1.0 / 60.0);
+                camera.add_trauma(0.1);
+            }
+            for (scrap, paddle, facing) in parried {
+                if let Some(side) = side_of(&paddle_sides, Some(paddle)) {
+                    events.push(GameEvent::new(Trigger::Parry, side));
+                }
+                if let Ok((transform, bullet)) =
+                    world.query_one_mut::<(&mut Transform, &mut Bullet)>(scrap)
+                {
+                    transform.velocity = (
+                        transform.velocity.0.abs() * facing * 1.5,
+                        transform.velocity.1,
+                    );
+                    bullet.owner = Some(paddle);
+                    particles.emit(
+                        &particles.preset("parry_flash"),
+                        transform.position,
+                        transform.velocity,
+                    );
+                }
+            }
+
+            // Knocked out paddles hand the point (or the match) to the other side.
+            for loser in knocked_out {
+                let position = world.get::<&Transform>(loser).unwrap().position;
+                let (winner, side) = if position.0 < arena_width() / 2.0 {
+                    (Phase::RightWin, Side::Right)
+                } else {
+                    (Phase::LeftWin, Side::Left)
+                };
+                particles.emit(&particles.preset("knockout_burst"), position, (0.0, 0.0));
+                sounds.push((Sound::BallGoal, 1.0, 0.6));
+                if game_state.phase == Phase::Ongoing {
+                    match side {
+                        Side::Left => game_state.left_score += 1,
+                        Side::Right => game_state.right_score += 1,
+                    }
+                    game_state.round.record_goal(winner);
+                    game_state.phase = winner;
+                    // (Under KO match, this takes the whole match. See `GameState::record_round`.)
+                    game_state.knocked_out = true;
+                    camera.hit_stop(20.0 / 60.0);
+                    camera.add_trauma(0.8);
+                    camera.punch(0.15);
+                }
+            }
+        }
+
+        // Balls bouncing around the arena.
+        if collide_balls(world, particles) > 0 {
+            sounds.push((
+                Sound::BallHitSide,
+                0.1,
+                rand::RandomRange::gen_range(0.6, 0.8),
+            ));
+        }
+
+        // Checking balls.
+        {
+            let entities: Vec<(Entity, Transform, Bounds, Option<PaddleActions>)> = world
+                .query::<(
+                    &Transform,
+                    &Bounds,
+                    Option<&PaddleHealth>,
+                    Option<&PaddleActions>,
+                )>()
+                .iter()
+                .filter(|(_e, (_i, _b, h, _a))| !h.is_some_and(|h| h.destroyed))
+                .map(|(e, (&i, &b, _h, a))| (e, i, b, a.copied())) // Copy out of the world
+                .collect::<Vec<_>>();
+            let mut shields: Vec<(Entity, bool)> = world
+                .query::<(&Transform, &TimedEffects)>()
+                .iter()
+                .filter(|(_e, (_t, effects))| effects.has(PowerUpKind::Shield))
+                .map(|(e, (t, _effects))| (e, t.position.0 < arena_width() / 2.0))
+                .collect::<Vec<_>>();
+            let mut shields_used: Vec<Entity> = Vec::new();
+            let mut grant_queue: Vec<(Entity, PowerUpKind)> = Vec::new();
+            let mut scored: Vec<Entity> = Vec::new();
+            game_state.intensity = 0.0; // Resetting the intensity.
+            for (id, (transform, ball)) in world.query_mut::<(&mut Transform, &mut Ball)>() {
+                // Spin bends the ball's path a little every step.
+                curve(transform, ball, game_state.time_scale);
+
+                // Outside the goal mouth, the ends are just more wall.
+                if (transform.position.0 > arena_width() || transform.position.0 < 0.0)
+                    && !level.in_goal(transform.position.1)
+                {
+                    transform.velocity.0 = if transform.position.0 < 0.0 {
+                        transform.velocity.0.abs()
+                    } else {
+                        -transform.velocity.0.abs()
+                    };
+                    transform.position.0 = transform.position.0.clamp(0.0, arena_width());
+                    sounds.push((
+                        Sound::BallHitSide,
+                        0.1,
+                        rand::RandomRange::gen_range(0.8, 1.0),
+                    ));
+                }
+
+                // Shields knock the ball back before it can reach the goal.
+                let goal_side = if transform.position.0 > arena_width() {
+                    Some(false)
+                } else if transform.position.0 < 0.0 {
+                    Some(true)
+                } else {
+                    None
+                };
+                if let Some(left_side) = goal_side {
+                    if let Some(index) = shields.iter().position(|s| s.1 == left_side) {
+                        shields_used.push(shields.remove(index).0);
+                        transform.velocity.0 = if left_side {
+                            transform.velocity.0.abs()
+                        } else {
+                            -transform.velocity.0.abs()
+                        };
+                        transform.position.0 = transform.position.0.clamp(0.0, arena_width());
+                        sounds.push((
+                            Sound::BallHitPaddle,
+                            0.15,
+                            rand::RandomRange::gen_range(0.5, 0.6),
+                        ));
+                    }
+                }
+
+                // Doing the simple collision checks.
+                if transform.position.0 > arena_width() && game_state.phase == Phase::Ongoing {
+                    game_state.round.record_goal(Phase::LeftWin);
+                    events.push(GameEvent::new(Trigger::Goal, Side::Left));
+                    camera.add_trauma(0.5);
+                    camera.punch(0.1);
+                    game_state.left_score += 1;
+                    let burst = particles
+                        .preset("goal_burst")
+                        .tint(theme.left)
+                        .intensity(transform.velocity.0.abs() + transform.velocity.1.abs());
+                    particles.emit(&burst, transform.position, transform.velocity);
+                    sounds.push((Sound::BallGoal, 1.0, 1.0));
+                    scored.push(id);
+                    continue;
+                }
+                if transform.position.0 < 0.0 && game_state.phase == Phase::Ongoing {
+                    game_state.round.record_goal(Phase::RightWin);
+                    events.push(GameEvent::new(Trigger::Goal, Side::Right));
+                    camera.add_trauma(0.5);
+                    camera.punch(0.1);
+                    game_state.right_score += 1;
+                    let burst = particles
+                        .preset("goal_burst")
+                        .tint(theme.right)
+                        .intensity(transform.velocity.0.abs() + transform.velocity.1.abs());
+                    particles.emit(&burst, transform.position, transform.velocity);
+                    sounds.push((Sound::BallGoal, 1.0, 1.0));
+                    scored.push(id);
+                    continue;
+                }
+                if transform.position.1 < 0.0 || transform.position.1 > arena_height() {
+                    transform.velocity.1 = -transform.velocity.1;
+                    spin_off_wall(ball, transform, transform.position.1 < 0.0);
+                    transform.position = (
+                        transform.position.0,
+                        transform.position.1.clamp(0.0, arena_height()),
+                    );
+                    sounds.push((
+                        Sound::BallHitSide,
+                        0.1,
+                        rand::RandomRange::gen_range(0.8, 1.0),
+                    ));
+                }
+
+                // Now checking against paddles.
+                for (paddle_id, paddle_transform, bounds, actions) in &entities {
+                    if test_sphere_capsule((transform, ball), (paddle_transform, bounds)) {
+                        ball.last_hit = Some(*paddle_id);
+                        ball.rally += 1;
+                        game_state.round.paddle_hits += 1;
+                        match_stats
+                            .side_mut(Side::of(paddle_transform.position.0))
+                            .returns += 1;
+                        if let Some(kind) = ball.carrying.take() {
+                            grant_queue.push((*paddle_id, kind));
+                        }
+                        match actions.filter(|a| a.is_parrying(current_time)) {
+                            // Parrying spikes the ball straight back, and hard.
+                            Some(actions) => {
+                                ball.speed = ball.speed + (1.5 / ball.speed);
+                                transform.velocity = (
+                                    actions.facing(),
+                                    (transform.position.1 - paddle_transform.position.1) / bounds.1
+                                        * 0.25,
+                                );
+                                camera.hit_stop(4.0 / 60.0);
+                                camera.add_trauma(0.3);
+                                events.push(GameEvent::new(
+                                    Trigger::Parry,
+                                    Side::of(paddle_transform.position.0),
+                                ));
+                            }
+                            None => {
+                                ball.speed = ball.speed + (0.5 / ball.speed);
+                                transform.velocity = (
+                                    (transform.position.0 - paddle_transform.position.0) / bounds.0
+                                        + (paddle_transform.velocity.0 * 0.25),
+                                    (transform.position.1 - paddle_transform.position.1) / bounds.1
+                                        + (paddle_transform.velocity.1 * 0.25),
+                                );
+                            }
+                        }
+                        let magnitude = (transform.velocity.0.powf(2.0)
+                            + transform.velocity.1.powf(2.0))
+                        .sqrt();
+                        transform.velocity = (
+                            (transform.velocity.0 / magnitude) * ball.speed,
+                            (transform.velocity.1 / magnitude) * ball.speed,
+                        );
+                        spin_from_paddle(ball, transform, paddle_transform);
+                        let spark = particles
+                            .preset("impact_spark")
+                            .count(transform.velocity.0.abs() as i32)
+                            .intensity(transform.velocity.0.abs());
+                        particles.emit(&spark, transform.position, transform.velocity);
+                        sounds.push((
+                            Sound::BallHitPaddle,
+                            0.15,
+                            rand::RandomRange::gen_range(0.8, 1.0),
+                        ));
+                        camera.hit_stop(ball.speed / 30.0);
+                        camera.add_trauma(ball.speed * 0.1);
+                        events.push(
+                            GameEvent::new(Trigger::Return, Side::of(paddle_transform.position.0))
+                                .speed(ball.speed),
+                        );
+                    }
+                }
+
+                // And updating our values.
+                game_state.intensity += ball.speed;
+                match_stats.track_ball(ball.speed, ball.rally);
+
+                // Oh and our particles.
+                // The preset's white, so it takes on the trail cosmetic. (Or the theme's colour.)
+                let trail = particles
+                    .preset("ball_trail")
+                    .lifetime(game_state.intensity / 4.0, 0.0)
+                    .tint(trail_tint.unwrap_or(theme.shadow));
+                particles.emit(&trail, transform.position, (0.0, 0.0));
+            }
+            game_state.intensity *= 4.0;
+            for ball in scored {
+                world.despawn(ball).unwrap();
+            }
+
+            for shield in shields_used {
+                if let Ok(mut effects) = world.get::<&mut TimedEffects>(shield) {
+                    effects
+                        .0
+                        .retain(|effect| effect.kind != PowerUpKind::Shield);
+                }
+            }
+            for (collector, kind) in grant_queue {
+                grant_power_up(world, collector, kind, current_time);
+            }
+
+            // Deciding the round, and topping up the balls in multiball.
+            let balls_left = world.query::<&Ball>().iter().count();
+            if game_state.phase == Phase::Ongoing {
+                if let Some(winner) = game_state.round.result(
+                    game_state.mode,
+                    &game_state.rules,
+                    balls_left,
+                    current_time,
+                ) {
+                    game_state.phase = winner;
+                } else if game_state.mode == GameMode::Multiball
+                    && game_state
+                        .round
+                        .wants_ball(&game_state.rules, balls_left, current_time)
+                {
+                    spawn_ball(world, random_serve_direction());
+                } else if game_state.mode == GameMode::Survival {
+                    if let Some(winner) = survival_result(world, &game_state.round) {
+                        game_state.phase = winner;
+                    } else if balls_left == 0 {
+                        // The AI let one in, so it's straight back at the player, a bit faster.
+                        let level = survival_level(current_time - game_state.round.started_at);
+                        serve_survival_ball(world, serve_speed(level));
+                    }
+                }
+            }
+
+            // Once the round is over, sweeping up any extra balls and power-ups.
+            if game_state.phase != Phase::Ongoing {
+                let leftovers = world
+                    .query::<&Ball>()
+                    .iter()
+                    .map(|(e, _)| e)
+                    .collect::<Vec<_>>();
+                for scrap in leftovers {
+                    world.despawn(scrap).unwrap();
+                }
+                clear_power_ups(world);
+            }
+        }
+    }
+
+    // Power-ups only show up mid-game, and effects wear off on their own.
+    if game_state.phase == Phase::Ongoing {
+        track_possession(world, match_stats, delta);
+        update_power_ups(world, &mut game_state.next_power_up, current_time);
+        regenerate_paddles(world, delta, current_time);
+    }
+    expire_effects(world, current_time);
+}
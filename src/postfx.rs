@@ -0,0 +1,295 @@
+use macroquad::prelude::*;
+
+use crate::particles::VERTEX_SHADER;
+
+// How far the colours split at full screen shake.
+const MAX_ABERRATION: f32 = 0.012;
+
+// Picking out the bright bits, blurring them and adding them back over the top.
+const BLOOM_SHADER: &str = "#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+uniform sampler2D Texture;
+uniform vec2 TexelSize;
+uniform float Threshold;
+uniform float Strength;
+vec3 bright(vec2 at) {
+    vec3 sample = texture2D(Texture, at).rgb;
+    float luma = dot(sample, vec3(0.299, 0.587, 0.114));
+    return sample * smoothstep(Threshold, 1.0, luma);
+}
+void main() {
+    vec3 glow = vec3(0.0);
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            glow += bright(uv + vec2(float(x), float(y)) * TexelSize * 2.0);
+        }
+    }
+    vec4 base = texture2D(Texture, uv);
+    gl_FragColor = vec4(base.rgb + glow / 49.0 * Strength, base.a) * color;
+}
+";
+
+// Splitting the red and blue channels outwards from the middle of the screen.
+const ABERRATION_SHADER: &str = "#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+uniform sampler2D Texture;
+uniform float Amount;
+void main() {
+    vec2 offset = (uv - 0.5) * Amount;
+    vec4 base = texture2D(Texture, uv);
+    float r = texture2D(Texture, uv + offset).r;
+    float b = texture2D(Texture, uv - offset).b;
+    gl_FragColor = vec4(r, base.g, b, base.a) * color;
+}
+";
+
+// A curved screen, scanlines and darker corners.
+const CRT_SHADER: &str = "#version 100
+precision mediump float;
+varying lowp vec4 color;
+varying lowp vec2 uv;
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+void main() {
+    vec2 centered = uv * 2.0 - 1.0;
+    centered *= 1.0 + dot(centered.yx, centered.yx) * 0.04;
+    vec2 at = centered * 0.5 + 0.5;
+    if (at.x < 0.0 || at.x > 1.0 || at.y < 0.0 || at.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec3 sample = texture2D(Texture, at).rgb;
+    float scanline = 0.8 + 0.2 * sin(at.y * Resolution.y * 3.14159);
+    float vignette = clamp(1.0 - dot(centered, centered) * 0.25, 0.0, 1.0);
+    gl_FragColor = vec4(sample * scanline * vignette, 1.0) * color;
+}
+";
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Effect {
+    Bloom,
+    Aberration,
+    Crt,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 3] = [Effect::Bloom, Effect::Aberration, Effect::Crt];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Effect::Bloom => "bloom",
+            Effect::Aberration => "aberration",
+            Effect::Crt => "CRT",
+        }
+    }
+
+    pub fn key(&self) -> KeyCode {
+        match self {
+            Effect::Bloom => KeyCode::B,
+            Effect::Aberration => KeyCode::C,
+            Effect::Crt => KeyCode::V,
+        }
+    }
+
+    fn shader(&self) -> &'static str {
+        match self {
+            Effect::Bloom => BLOOM_SHADER,
+            Effect::Aberration => ABERRATION_SHADER,
+            Effect::Crt => CRT_SHADER,
+        }
+    }
+
+    fn uniforms(&self) -> Vec<UniformDesc> {
+        match self {
+            Effect::Bloom => vec![
+                UniformDesc::new("TexelSize", UniformType::Float2),
+                UniformDesc::new("Threshold", UniformType::Float1),
+                UniformDesc::new("Strength", UniformType::Float1),
+            ],
+            Effect::Aberration => vec![UniformDesc::new("Amount", UniformType::Float1)],
+            Effect::Crt => vec![UniformDesc::new("Resolution", UniformType::Float2)],
+        }
+    }
+}
+
+// One effect, whether it's switched on, and its material. (None if the shader didn't compile.)
+struct Pass {
+    effect: Effect,
+    enabled: bool,
+    material: Option<Material>,
+}
+
+// Drawing the scene into a texture, then running it through each enabled effect on its way to the
+// screen. With nothing switched on the scene just goes straight to the screen as usual.
+pub struct PostProcessing {
+    passes: Vec<Pass>,
+    targets: Vec<RenderTarget>, // The scene, and a spare to bounce between passes.
+    active: bool,               // Whether this frame is being drawn into a target.
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostProcessing {
+    pub fn new() -> Self {
+        let passes = Effect::ALL
+            .iter()
+            .map(|&effect| Pass {
+                effect,
+                enabled: false,
+                material: load_material(
+                    ShaderSource::Glsl {
+                        vertex: VERTEX_SHADER,
+                        fragment: effect.shader(),
+                    },
+                    MaterialParams {
+                        uniforms: effect.uniforms(),
+                        ..Default::default()
+                    },
+                )
+                .map_err(|error| {
+                    eprintln!(
+                        "Couldn't build the {} shader. ({:?})",
+                        effect.label(),
+                        error
+                    )
+                })
+                .ok(),
+            })
+            .collect();
+        PostProcessing {
+            passes,
+            targets: Vec::new(),
+            active: false,
+        }
+    }
+
+    // Whether an effect can be used at all.
+    pub fn available(&self, effect: Effect) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.effect == effect && pass.material.is_some())
+    }
+
+    pub fn enabled(&self, effect: Effect) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.effect == effect && pass.enabled && pass.material.is_some())
+    }
+
+    pub fn toggle(&mut self, effect: Effect) {
+        for pass in self.passes.iter_mut().filter(|pass| pass.effect == effect) {
+            pass.enabled = !pass.enabled;
+        }
+    }
+
+    // What to show in the options line for an effect.
+    pub fn status(&self, effect: Effect) -> &'static str {
+        if !self.available(effect) {
+            "unavailable"
+        } else if self.enabled(effect) {
+            "on"
+        } else {
+            "off"
+        }
+    }
+
+    // The texture this frame's scene should be drawn into, if there's post-processing to do.
+    pub fn scene_target(&self) -> Option<RenderTarget> {
+        self.active.then(|| self.targets[0].clone())
+    }
+
+    // Getting ready for the frame, making sure there's a scene texture if anything's enabled.
+    pub fn begin(&mut self) {
+        self.active = self
+            .passes
+            .iter()
+            .any(|pass| pass.enabled && pass.material.is_some());
+        if !self.active {
+            return;
+        }
+        let size = (screen_width() as u32, screen_height() as u32);
+        if self.targets.first().map(|target| {
+            (
+                target.texture.width() as u32,
+                target.texture.height() as u32,
+            )
+        }) != Some(size)
+        {
+            self.targets = (0..2)
+                .map(|_| {
+                    let target = render_target(size.0, size.1);
+                    target.texture.set_filter(FilterMode::Linear);
+                    target
+                })
+                .collect();
+        }
+    }
+
+    // Running the scene through every enabled effect and onto the screen. `shake` is how hard the
+    // screen's shaking, from 0 to 1.
+    pub fn finish(&mut self, shake: f32) {
+        if !self.active {
+            set_default_camera();
+            return;
+        }
+        let (width, height) = (screen_width(), screen_height());
+        let passes = self
+            .passes
+            .iter()
+            .filter_map(|pass| match (&pass.material, pass.enabled) {
+                (Some(material), true) => Some((pass.effect, material)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut source = 0;
+        for (index, (effect, material)) in passes.iter().enumerate() {
+            match effect {
+                Effect::Bloom => {
+                    material.set_uniform("TexelSize", vec2(1.0 / width, 1.0 / height));
+                    material.set_uniform("Threshold", 0.6_f32);
+                    material.set_uniform("Strength", 1.5_f32);
+                }
+                Effect::Aberration => material.set_uniform("Amount", shake * MAX_ABERRATION),
+                Effect::Crt => material.set_uniform("Resolution", vec2(width, height)),
+            }
+            // The last pass goes to the screen, the rest bounce between the two targets.
+            if index + 1 == passes.len() {
+                set_default_camera();
+            } else {
+                set_camera(&target_camera(self.targets[1 - source].clone()));
+            }
+            gl_use_material(material);
+            draw_texture_ex(
+                &self.targets[source].texture,
+                0.0,
+                0.0,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(width, height)),
+                    ..Default::default()
+                },
+            );
+            gl_use_default_material();
+            source = 1 - source;
+        }
+        self.active = false;
+    }
+}
+
+// A camera covering the screen, drawing into a texture.
+fn target_camera(target: RenderTarget) -> Camera2D {
+    Camera2D {
+        zoom: vec2(2.0 / screen_width(), 2.0 / screen_height()),
+        target: vec2(screen_width() / 2.0, screen_height() / 2.0),
+        render_target: Some(target),
+        ..Default::default()
+    }
+}
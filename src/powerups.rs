@@ -0,0 +1,401 @@
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::arena::{arena_height, arena_width};
+use crate::health::PaddleHealth;
+use crate::hud::{Anchor, Hud};
+use crate::sprites::{Sprite, SpriteAtlas, POWER_UP_SPRITE};
+use crate::theme::Theme;
+use crate::{
+    random_serve_direction, spawn_ball, square_distance, Ball, Bounds, Bullet, ControlType,
+    ParticleStorage, Transform,
+};
+
+// How long (in seconds) between power-ups showing up, and how many can float around at once.
+pub const POWER_UP_INTERVAL: f64 = 8.0;
+const MAX_POWER_UPS: usize = 2;
+
+// How much a grown paddle gets extended by.
+const GROW_AMOUNT: f32 = 32.0;
+
+// Every flavour of power-up.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PowerUpKind {
+    Grow,
+    Shield,
+    Multiball,
+    SlowMo,
+    Reverse,
+    RapidFire,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::Grow,
+        PowerUpKind::Shield,
+        PowerUpKind::Multiball,
+        PowerUpKind::SlowMo,
+        PowerUpKind::Reverse,
+        PowerUpKind::RapidFire,
+    ];
+
+    pub fn random() -> Self {
+        Self::ALL[rand::gen_range(0, Self::ALL.len())]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::Grow => "GROW",
+            PowerUpKind::Shield => "SHIELD",
+            PowerUpKind::Multiball => "MULTI",
+            PowerUpKind::SlowMo => "SLOW",
+            PowerUpKind::Reverse => "REVERSE",
+            PowerUpKind::RapidFire => "RAPID",
+        }
+    }
+
+    // How long the effect lasts. (Multiball is instant, so it has none.)
+    pub fn duration(&self) -> f64 {
+        match self {
+            PowerUpKind::Grow => 10.0,
+            PowerUpKind::Shield => 15.0,
+            PowerUpKind::Multiball => 0.0,
+            PowerUpKind::SlowMo => 6.0,
+            PowerUpKind::Reverse => 5.0,
+            PowerUpKind::RapidFire => 8.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUpKind::Grow => GREEN,
+            PowerUpKind::Shield => SKYBLUE,
+            PowerUpKind::Multiball => GOLD,
+            PowerUpKind::SlowMo => VIOLET,
+            PowerUpKind::Reverse => ORANGE,
+            PowerUpKind::RapidFire => PINK,
+        }
+    }
+}
+
+// The pickup itself, floating around midfield.
+#[derive(Clone, Copy)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct TimedEffect {
+    pub kind: PowerUpKind,
+    pub expires_at: f64,
+}
+
+// A component for tracking whatever effects a paddle currently has going.
+#[derive(Default, Clone)]
+pub struct TimedEffects(pub Vec<TimedEffect>);
+
+impl TimedEffects {
+    pub fn has(&self, kind: PowerUpKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    // Adds an effect, or refreshes it if it's already running. Returns true if it's new.
+    fn add(&mut self, kind: PowerUpKind, current_time: f64) -> bool {
+        let expires_at = current_time + kind.duration();
+        match self.0.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => {
+                effect.expires_at = expires_at;
+                false
+            }
+            None => {
+                self.0.push(TimedEffect { kind, expires_at });
+                true
+            }
+        }
+    }
+}
+
+// Dropping a new power-up somewhere around the middle of the arena.
+pub fn spawn_power_up(world: &mut World) {
+    let kind = PowerUpKind::random();
+    world.spawn((
+        Transform {
+            position: (
+                arena_width() / 2.0 + rand::gen_range(-arena_width() / 8.0, arena_width() / 8.0),
+                rand::gen_range(arena_height() * 0.15, arena_height() * 0.85),
+            ),
+            velocity: (0.0, rand::gen_range(-0.3, 0.3)),
+        },
+        PowerUp { kind, radius: 20.0 },
+        Sprite::new(POWER_UP_SPRITE).tint(kind.color()),
+    ));
+}
+
+// Spawning on a timer and keeping the pickups inside the arena.
+pub fn update_power_ups(world: &mut World, next_spawn: &mut f64, current_time: f64) {
+    let mut count = 0;
+    for (_id, (transform, _power_up)) in world.query_mut::<(&mut Transform, &PowerUp)>() {
+        if transform.position.1 < 0.0 || transform.position.1 > arena_height() {
+            transform.velocity.1 = -transform.velocity.1;
+            transform.position.1 = transform.position.1.clamp(0.0, arena_height());
+        }
+        count += 1;
+    }
+    if current_time > *next_spawn {
+        if count < MAX_POWER_UPS {
+            spawn_power_up(world);
+        }
+        *next_spawn = current_time + POWER_UP_INTERVAL;
+    }
+}
+
+// Handing a power-up to a paddle. Returns false if there was nobody to give it to.
+pub fn grant_power_up(
+    world: &mut World,
+    collector: Entity,
+    kind: PowerUpKind,
+    current_time: f64,
+) -> bool {
+    if !world.contains(collector) {
+        return false;
+    }
+    match kind {
+        PowerUpKind::Multiball => {
+            spawn_ball(world, random_serve_direction());
+        }
+        PowerUpKind::Reverse => {
+            // This one is for the other guy.
+            let opponents = world
+                .query::<&ControlType>()
+                .iter()
+                .map(|(e, _)| e)
+                .filter(|&e| e != collector)
+                .collect::<Vec<_>>();
+            for opponent in opponents {
+                if let Ok(mut effects) = world.get::<&mut TimedEffects>(opponent) {
+                    effects.add(kind, current_time);
+                }
+            }
+        }
+        _ => {
+            let is_new = match world.get::<&mut TimedEffects>(collector) {
+                Ok(mut effects) => effects.add(kind, current_time),
+                Err(_) => return false,
+            };
+            if is_new && kind == PowerUpKind::Grow {
+                if let Ok(mut bounds) = world.get::<&mut Bounds>(collector) {
+                    bounds.1 += GROW_AMOUNT;
+                }
+                if let Ok(mut health) = world.get::<&mut PaddleHealth>(collector) {
+                    health.max_length += GROW_AMOUNT;
+                }
+            }
+        }
+    }
+    true
+}
+
+// Removing anything that has run out, and undoing what needs undoing.
+pub fn expire_effects(world: &mut World, current_time: f64) {
+    for (_id, (effects, bounds, mut health)) in
+        world.query_mut::<(&mut TimedEffects, &mut Bounds, Option<&mut PaddleHealth>)>()
+    {
+        effects.0.retain(|effect| {
+            let alive = effect.expires_at > current_time;
+            if !alive && effect.kind == PowerUpKind::Grow {
+                match health.as_deref_mut() {
+                    Some(health) => {
+                        health.max_length -= GROW_AMOUNT;
+                        bounds.1 = (bounds.1 - GROW_AMOUNT).max(health.min_length);
+                    }
+                    None => bounds.1 = (bounds.1 - GROW_AMOUNT).max(0.0),
+                }
+            }
+            alive
+        });
+    }
+}
+
+// Shots and balls picking up power-ups. Returns how many got collected.
+pub fn collect_power_ups(
+    world: &mut World,
+    particles: &mut ParticleStorage,
+    current_time: f64,
+) -> i32 {
+    let power_ups = world
+        .query::<(&Transform, &PowerUp)>()
+        .iter()
+        .map(|(e, (&t, &p))| (e, t, p)) // Copy out of the world
+        .collect::<Vec<_>>();
+    let mut collected = 0;
+    let mut despawn_queue: Vec<Entity> = Vec::new();
+    let mut grant_queue: Vec<(Entity, PowerUpKind)> = Vec::new();
+    for (power_up_id, power_up_transform, power_up) in &power_ups {
+        let mut taken = false;
+        // Shooting it hands it straight to the shooter.
+        for (bullet_id, (transform, bullet)) in world.query_mut::<(&Transform, &Bullet)>() {
+            if !taken
+                && square_distance(
+                    transform.position.0,
+                    transform.position.1,
+                    power_up_transform.position.0,
+                    power_up_transform.position.1,
+                ) < (bullet.radius + power_up.radius).powf(2.0)
+            {
+                taken = true;
+                despawn_queue.push(bullet_id);
+                if let Some(owner) = bullet.owner {
+                    grant_queue.push((owner, power_up.kind));
+                }
+            }
+        }
+        // Balls carry it along until the next paddle touches them.
+        for (_id, (transform, ball)) in world.query_mut::<(&Transform, &mut Ball)>() {
+            if !taken
+                && ball.carrying.is_none()
+                && square_distance(
+                    transform.position.0,
+                    transform.position.1,
+                    power_up_transform.position.0,
+                    power_up_transform.position.1,
+                ) < (ball.radius + power_up.radius).powf(2.0)
+            {
+                taken = true;
+                ball.carrying = Some(power_up.kind);
+            }
+        }
+        if taken {
+            collected += 1;
+            despawn_queue.push(*power_up_id);
+            let pop = particles.preset("power_up_pop").tint(power_up.kind.color());
+            particles.emit(&pop, power_up_transform.position, (0.0, 0.0));
+        }
+    }
+    for scrap in despawn_queue {
+        let _ = world.despawn(scrap);
+    }
+    for (collector, kind) in grant_queue {
+        grant_power_up(world, collector, kind, current_time);
+    }
+    collected
+}
+
+// Clearing out every pickup and effect, for between rounds.
+pub fn clear_power_ups(world: &mut World) {
+    let pickups = world
+        .query::<&PowerUp>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    for scrap in pickups {
+        world.despawn(scrap).unwrap();
+    }
+    for (_id, effects) in world.query_mut::<&mut TimedEffects>() {
+        effects.0.clear();
+    }
+}
+
+// Drawing the pickups, plus anything a ball happens to be carrying.
+pub fn draw_power_ups(world: &mut World, current_time: f64, theme: &Theme, atlas: &SpriteAtlas) {
+    for (_id, (transform, power_up, sprite)) in
+        world.query_mut::<(&Transform, &PowerUp, Option<&Sprite>)>()
+    {
+        let pulse = ((current_time * 6.0).sin() as f32) * 2.0;
+        let size = (power_up.radius + pulse) * 2.0;
+        let skinned = sprite.is_some_and(|sprite| {
+            atlas.draw(sprite, transform.position, (size, size), 0.0, current_time)
+        });
+        if !skinned {
+            draw_circle(
+                transform.position.0,
+                transform.position.1,
+                power_up.radius + pulse,
+                theme.shadow,
+            );
+            draw_circle_lines(
+                transform.position.0,
+                transform.position.1,
+                power_up.radius + pulse,
+                3.0,
+                power_up.kind.color(),
+            );
+        }
+        let letter = &power_up.kind.label()[0..1];
+        let text_size = measure_text(letter, None, 24, 1.0);
+        draw_text(
+            letter,
+            transform.position.0 - text_size.width / 2.0,
+            transform.position.1 + text_size.height / 2.0,
+            24.0,
+            power_up.kind.color(),
+        );
+    }
+    for (_id, (transform, ball)) in world.query_mut::<(&Transform, &Ball)>() {
+        if let Some(kind) = ball.carrying {
+            draw_circle(
+                transform.position.0,
+                transform.position.1,
+                ball.radius / 2.0,
+                kind.color(),
+            );
+        }
+    }
+}
+
+// Drawing the shields, and the list of running effects above each paddle's half.
+pub fn draw_effects_hud(world: &mut World, current_time: f64, hud: &Hud) {
+    for (_id, (transform, effects)) in world.query_mut::<(&Transform, &TimedEffects)>() {
+        let left_side = transform.position.0 < screen_width() / 2.0;
+        if effects.has(PowerUpKind::Shield) {
+            let x = if left_side { 2.0 } else { screen_width() - 2.0 };
+            draw_line(x, 0.0, x, screen_height(), 4.0, PowerUpKind::Shield.color());
+        }
+        let (anchor, x) = if left_side {
+            (Anchor::TopLeft, 32.0)
+        } else {
+            (Anchor::TopRight, -32.0)
+        };
+        for (i, effect) in effects.0.iter().enumerate() {
+            let y = 24.0 + i as f32 * 28.0;
+            let remaining =
+                ((effect.expires_at - current_time) / effect.kind.duration()).clamp(0.0, 1.0);
+            hud.text(
+                effect.kind.label(),
+                anchor,
+                (x, y),
+                20.0,
+                effect.kind.color(),
+            );
+            hud.bar(
+                anchor,
+                (x, y + 18.0),
+                (128.0, 4.0),
+                remaining as f32,
+                effect.kind.color(),
+            );
+        }
+    }
+}
+
+// Where the paddles with slow-mo running are, horizontally.
+pub fn slowed_sides(world: &World) -> Vec<f32> {
+    world
+        .query::<(&Transform, &TimedEffects)>()
+        .iter()
+        .filter(|(_e, (_t, effects))| effects.has(PowerUpKind::SlowMo))
+        .map(|(_e, (t, _effects))| t.position.0)
+        .collect::<Vec<_>>()
+}
+
+// Balls heading towards a slowed paddle move at half speed.
+pub fn slow_mo_scale(slowed_sides: &[f32], transform: &Transform) -> f32 {
+    let heading_towards = slowed_sides
+        .iter()
+        .any(|&side| (side - transform.position.0).signum() == transform.velocity.0.signum());
+    if heading_towards {
+        0.5
+    } else {
+        1.0
+    }
+}
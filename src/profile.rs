@@ -0,0 +1,501 @@
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::theme::Theme;
+use crate::Bounds;
+
+// Where the local player's progress is kept, relative to wherever the game is launched from.
+pub const PROFILE_FILE: &str = "profile.txt";
+
+// What it takes to unlock a cosmetic.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Unlock {
+    Free,
+    Games(u32),
+    Wins(u32),
+}
+
+// Something to show off with, and what it takes to get it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cosmetic<T> {
+    pub name: &'static str,
+    pub unlock: Unlock,
+    pub look: T,
+}
+
+// How the player's paddle gets filled in.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PaddleStyle {
+    Outline,
+    Solid,
+    Striped,
+    Split,
+}
+
+// What gets done to the ball's trail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailStyle {
+    Preset,
+    Tinted(Color),
+    Rainbow,
+}
+
+pub const PADDLE_SKINS: [Cosmetic<PaddleStyle>; 4] = [
+    Cosmetic {
+        name: "Classic",
+        unlock: Unlock::Free,
+        look: PaddleStyle::Outline,
+    },
+    Cosmetic {
+        name: "Striped",
+        unlock: Unlock::Games(3),
+        look: PaddleStyle::Striped,
+    },
+    Cosmetic {
+        name: "Solid",
+        unlock: Unlock::Wins(1),
+        look: PaddleStyle::Solid,
+    },
+    Cosmetic {
+        name: "Split",
+        unlock: Unlock::Wins(5),
+        look: PaddleStyle::Split,
+    },
+];
+
+pub const BALL_TRAILS: [Cosmetic<TrailStyle>; 4] = [
+    Cosmetic {
+        name: "Classic",
+        unlock: Unlock::Free,
+        look: TrailStyle::Preset,
+    },
+    Cosmetic {
+        name: "Ember",
+        unlock: Unlock::Games(5),
+        look: TrailStyle::Tinted(ORANGE),
+    },
+    Cosmetic {
+        name: "Frost",
+        unlock: Unlock::Wins(3),
+        look: TrailStyle::Tinted(SKYBLUE),
+    },
+    Cosmetic {
+        name: "Rainbow",
+        unlock: Unlock::Wins(10),
+        look: TrailStyle::Rainbow,
+    },
+];
+
+// None keeps the theme's colour.
+pub const BULLET_COLOURS: [Cosmetic<Option<Color>>; 4] = [
+    Cosmetic {
+        name: "Classic",
+        unlock: Unlock::Free,
+        look: None,
+    },
+    Cosmetic {
+        name: "Gold",
+        unlock: Unlock::Games(1),
+        look: Some(GOLD),
+    },
+    Cosmetic {
+        name: "Lime",
+        unlock: Unlock::Wins(2),
+        look: Some(LIME),
+    },
+    Cosmetic {
+        name: "Magenta",
+        unlock: Unlock::Games(10),
+        look: Some(MAGENTA),
+    },
+];
+
+// The local player's record, and what they've picked to show off with.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub games_played: u32,
+    pub wins: u32,
+    pub achievements: Vec<String>,
+    pub paddle_skin: usize, // Indices into the cosmetic lists.
+    pub ball_trail: usize,
+    pub bullet_colour: usize,
+}
+
+impl Profile {
+    pub fn parse(source: &str) -> Result<Profile, String> {
+        let mut profile = Profile::default();
+        for (index, raw) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = raw.split('#').next().unwrap_or("").trim();
+            let Some((keyword, value)) = text.split_once(' ') else {
+                if text.is_empty() {
+                    continue;
+                }
+                return Err(format!("line {}: '{}' is missing a value", line, text));
+            };
+            let value = value.trim();
+            let count = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("line {}: '{}' isn't a count", line, value))
+            };
+            match keyword {
+                "games" => profile.games_played = count()?,
+                "wins" => profile.wins = count()?,
+                "achievement" => profile.achievements.push(value.to_string()),
+                "paddle_skin" => profile.paddle_skin = index_of(&PADDLE_SKINS, value),
+                "ball_trail" => profile.ball_trail = index_of(&BALL_TRAILS, value),
+                "bullet_colour" => profile.bullet_colour = index_of(&BULLET_COLOURS, value),
+                // Likely from a newer version, so it's skipped rather than losing everything else.
+                _ => {}
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("games {}\nwins {}\n", self.games_played, self.wins);
+        for achievement in &self.achievements {
+            text += &format!("achievement {}\n", achievement);
+        }
+        text += &format!(
+            "paddle_skin {}\nball_trail {}\nbullet_colour {}\n",
+            PADDLE_SKINS[self.paddle_skin].name,
+            BALL_TRAILS[self.ball_trail].name,
+            BULLET_COLOURS[self.bullet_colour].name
+        );
+        text
+    }
+
+    // Loading the profile, starting afresh if there isn't one yet. One that can't be read gets
+    // moved aside first, so the next save doesn't wipe it out.
+    pub fn load(path: &Path) -> Profile {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Profile::parse(&source).unwrap_or_else(|error| {
+                let backup = path.with_extension("bak");
+                match std::fs::rename(path, &backup) {
+                    Ok(()) => eprintln!(
+                        "Couldn't read the profile, so it's been moved to {} and a new one started. ({}: {})",
+                        backup.display(),
+                        path.display(),
+                        error
+                    ),
+                    Err(rename_error) => eprintln!(
+                        "Couldn't read the profile, or move it aside ({}), so a new one will replace it. ({}: {})",
+                        rename_error,
+                        path.display(),
+                        error
+                    ),
+                }
+                Profile::default()
+            }),
+            Err(_) => Profile::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_text())
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // Saving, and complaining (but carrying on) if it doesn't work out.
+    pub fn save_or_warn(&self, path: &Path) {
+        if let Err(error) = self.save(path) {
+            eprintln!("Couldn't save the profile. ({})", error);
+        }
+    }
+
+    pub fn record_game(&mut self, won: bool) {
+        self.games_played += 1;
+        if won {
+            self.wins += 1;
+        }
+    }
+
+    // Returns true if it's new.
+    pub fn unlock_achievement(&mut self, id: &str) -> bool {
+        if self
+            .achievements
+            .iter()
+            .any(|achievement| achievement == id)
+        {
+            return false;
+        }
+        self.achievements.push(id.to_string());
+        true
+    }
+
+    pub fn unlocked(&self, unlock: Unlock) -> bool {
+        match unlock {
+            Unlock::Free => true,
+            Unlock::Games(games) => self.games_played >= games,
+            Unlock::Wins(wins) => self.wins >= wins,
+        }
+    }
+
+    // The next cosmetic along that's been unlocked, wrapping around to the free one.
+    fn cycle<T>(&self, current: usize, list: &[Cosmetic<T>]) -> usize {
+        (1..=list.len())
+            .map(|step| (current + step) % list.len())
+            .find(|&index| self.unlocked(list[index].unlock))
+            .unwrap_or(0)
+    }
+
+    pub fn next_paddle_skin(&mut self) {
+        self.paddle_skin = self.cycle(self.paddle_skin, &PADDLE_SKINS);
+    }
+
+    pub fn next_ball_trail(&mut self) {
+        self.ball_trail = self.cycle(self.ball_trail, &BALL_TRAILS);
+    }
+
+    pub fn next_bullet_colour(&mut self) {
+        self.bullet_colour = self.cycle(self.bullet_colour, &BULLET_COLOURS);
+    }
+
+    // What's being worn, as long as it's actually been unlocked.
+    fn chosen<T: Copy>(&self, index: usize, list: &[Cosmetic<T>]) -> Cosmetic<T> {
+        match list.get(index) {
+            Some(cosmetic) if self.unlocked(cosmetic.unlock) => *cosmetic,
+            _ => list[0],
+        }
+    }
+
+    pub fn paddle_skin(&self) -> Cosmetic<PaddleStyle> {
+        self.chosen(self.paddle_skin, &PADDLE_SKINS)
+    }
+
+    pub fn ball_trail(&self) -> Cosmetic<TrailStyle> {
+        self.chosen(self.ball_trail, &BALL_TRAILS)
+    }
+
+    pub fn bullet_colour(&self) -> Cosmetic<Option<Color>> {
+        self.chosen(self.bullet_colour, &BULLET_COLOURS)
+    }
+
+    // How many of everything there is to unlock have been.
+    pub fn unlock_count(&self) -> (usize, usize) {
+        let unlocks = PADDLE_SKINS
+            .iter()
+            .map(|skin| skin.unlock)
+            .chain(BALL_TRAILS.iter().map(|trail| trail.unlock))
+            .chain(BULLET_COLOURS.iter().map(|colour| colour.unlock))
+            .collect::<Vec<_>>();
+        (
+            unlocks
+                .iter()
+                .filter(|&&unlock| self.unlocked(unlock))
+                .count(),
+            unlocks.len(),
+        )
+    }
+}
+
+// Where a cosmetic is in its list. Unknown ones (from an older or newer version) just go back to
+// the default.
+fn index_of<T>(list: &[Cosmetic<T>], name: &str) -> usize {
+    list.iter()
+        .position(|cosmetic| cosmetic.name == name)
+        .unwrap_or(0)
+}
+
+// Drawing a paddle in one of the unlockable styles, as the capsule it collides as. `outline` is
+// the colour it'd normally have.
+pub fn draw_paddle(
+    style: PaddleStyle,
+    position: (f32, f32),
+    bounds: &Bounds,
+    outline: Color,
+    theme: &Theme,
+) {
+    let shape = bounds.shape();
+    let (top, bottom) = shape.endpoints(position);
+    let radius = shape.radius();
+    let faint = Color { a: 0.4, ..outline };
+    // The straight middle, and a circle on each end.
+    let fill = |from: f32, to: f32, color: Color| {
+        draw_rectangle(position.0 - radius, from, radius * 2.0, to - from, color);
+        draw_circle(position.0, from, radius, color);
+        draw_circle(position.0, to, radius, color);
+    };
+    fill(top.1, bottom.1, theme.shadow);
+    match style {
+        PaddleStyle::Outline => {}
+        PaddleStyle::Solid => fill(top.1, bottom.1, faint),
+        PaddleStyle::Striped => {
+            let mut stripe = top.1 - radius + 6.0;
+            while stripe < bottom.1 + radius - 4.0 {
+                // Only as wide as the capsule is, this far down.
+                let off_end = stripe - stripe.clamp(top.1, bottom.1);
+                let width = (radius * radius - off_end * off_end).max(0.0).sqrt();
+                draw_line(
+                    position.0 - width,
+                    stripe,
+                    position.0 + width,
+                    stripe,
+                    2.0,
+                    faint,
+                );
+                stripe += 12.0;
+            }
+        }
+        PaddleStyle::Split => {
+            draw_rectangle(
+                position.0 - radius,
+                top.1,
+                radius * 2.0,
+                position.1 - top.1,
+                faint,
+            );
+            draw_arc(position.0, top.1, 20, 0.0, 180.0, radius, 180.0, faint);
+        }
+    }
+    // The sides, and the round ends. (Arcs draw outwards, so they start in by the thickness.)
+    for side in [-1.0, 1.0] {
+        let x = position.0 + side * (radius - 2.0);
+        draw_line(x, top.1, x, bottom.1, 4.0, outline);
+    }
+    draw_arc(
+        position.0,
+        top.1,
+        20,
+        radius - 4.0,
+        180.0,
+        4.0,
+        180.0,
+        outline,
+    );
+    draw_arc(
+        position.0,
+        bottom.1,
+        20,
+        radius - 4.0,
+        0.0,
+        4.0,
+        180.0,
+        outline,
+    );
+}
+
+// The colour a trail gets tinted, if any.
+pub fn trail_tint(style: TrailStyle, current_time: f64) -> Option<Color> {
+    match style {
+        TrailStyle::Preset => None,
+        TrailStyle::Tinted(color) => Some(color),
+        TrailStyle::Rainbow => Some(macroquad::color::hsl_to_rgb(
+            (current_time * 0.5).fract() as f32,
+            1.0,
+            0.6,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(games_played: u32, wins: u32) -> Profile {
+        Profile {
+            games_played,
+            wins,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let profile = Profile {
+            games_played: 12,
+            wins: 7,
+            achievements: vec!["first_win".to_string(), "deflector".to_string()],
+            paddle_skin: 3,
+            ball_trail: 2,
+            bullet_colour: 1,
+        };
+        assert_eq!(Profile::parse(&profile.to_text()), Ok(profile.clone()));
+        assert_eq!(
+            Profile::parse(&Profile::default().to_text()),
+            Ok(Profile::default())
+        );
+    }
+
+    #[test]
+    fn skips_settings_it_doesnt_know() {
+        let parsed = Profile::parse("games 4\nfavourite_colour Teal\nwins 2\n").unwrap();
+        assert_eq!(parsed, profile(4, 2));
+    }
+
+    #[test]
+    fn unknown_cosmetics_go_back_to_the_default() {
+        let parsed = Profile::parse("paddle_skin Sparkly\nball_trail Frost\n").unwrap();
+        assert_eq!(parsed.paddle_skin, 0);
+        assert_eq!(parsed.ball_trail, 2);
+    }
+
+    #[test]
+    fn bad_lines() {
+        assert_eq!(
+            Profile::parse("games lots"),
+            Err("line 1: 'lots' isn't a count".to_string())
+        );
+        assert_eq!(
+            Profile::parse("# Comment.\nwins"),
+            Err("line 2: 'wins' is missing a value".to_string())
+        );
+    }
+
+    #[test]
+    fn unreadable_profiles_get_moved_aside() {
+        let path = std::env::temp_dir().join(format!("pwg-profile-{}.txt", std::process::id()));
+        let backup = path.with_extension("bak");
+        std::fs::write(&path, "games 20\nwins many\n").unwrap();
+        assert_eq!(Profile::load(&path), Profile::default());
+        assert!(!path.exists());
+        let kept = std::fs::read_to_string(&backup).unwrap();
+        std::fs::remove_file(&backup).unwrap();
+        assert_eq!(kept, "games 20\nwins many\n");
+    }
+
+    #[test]
+    fn cycling_skips_locked_cosmetics() {
+        // Three games unlocks Striped, but Solid and Split need wins.
+        let mut player = profile(3, 0);
+        player.next_paddle_skin();
+        assert_eq!(player.paddle_skin().name, "Striped");
+        player.next_paddle_skin();
+        assert_eq!(player.paddle_skin().name, "Classic");
+        // With everything unlocked, it goes through the lot in order.
+        let mut player = profile(100, 100);
+        let names = (0..PADDLE_SKINS.len())
+            .map(|_| {
+                player.next_paddle_skin();
+                player.paddle_skin().name
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Striped", "Solid", "Split", "Classic"]);
+        // And with nothing, it stays put.
+        let mut player = profile(0, 0);
+        player.next_ball_trail();
+        assert_eq!(player.ball_trail, 0);
+    }
+
+    #[test]
+    fn locked_choices_fall_back_to_the_default() {
+        // A profile file that picks something it hasn't earned (yet).
+        let player = Profile {
+            paddle_skin: 3,
+            ball_trail: 3,
+            bullet_colour: 99,
+            ..profile(1, 1)
+        };
+        assert_eq!(player.paddle_skin().name, "Classic");
+        assert_eq!(player.ball_trail().name, "Classic");
+        assert_eq!(player.bullet_colour().name, "Classic");
+        let player = Profile {
+            paddle_skin: 2,
+            ..profile(1, 1)
+        };
+        assert_eq!(player.paddle_skin().name, "Solid");
+    }
+}
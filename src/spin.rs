@@ -0,0 +1,151 @@
+use macroquad::prelude::*;
+
+use crate::theme::Theme;
+use crate::{Ball, Transform};
+
+// How hard spin bends the ball's path, and how quickly it wears off.
+const MAGNUS: f32 = 0.05;
+const SPIN_DECAY: f32 = 0.997;
+const MAX_SPIN: f32 = 0.08;
+
+// How much spin the different impacts hand out.
+const PADDLE_SPIN: f32 = 0.01;
+const BULLET_SPIN: f32 = 0.004;
+const WALL_GRIP: f32 = 4.0;
+
+// Keeping the ball at its proper speed after something has nudged its direction.
+pub fn renormalise(transform: &mut Transform, speed: f32) {
+    let magnitude = (transform.velocity.0.powf(2.0) + transform.velocity.1.powf(2.0)).sqrt();
+    if magnitude > 0.0 {
+        transform.velocity = (
+            (transform.velocity.0 / magnitude) * speed,
+            (transform.velocity.1 / magnitude) * speed,
+        );
+    }
+}
+
+fn add_spin(ball: &mut Ball, amount: f32) {
+    ball.spin = (ball.spin + amount).clamp(-MAX_SPIN, MAX_SPIN);
+}
+
+// Bending the ball sideways to its motion (Magnus-style), once per step. `scale` is how much of a
+// step it is, so slow-mo bends it along the same path, just slower.
+pub fn curve(transform: &mut Transform, ball: &mut Ball, scale: f32) {
+    let (sin, cos) = (ball.spin * MAGNUS * scale).sin_cos();
+    transform.velocity = (
+        transform.velocity.0 * cos - transform.velocity.1 * sin,
+        transform.velocity.1 * cos + transform.velocity.0 * sin,
+    );
+    renormalise(transform, ball.speed);
+    ball.angle += ball.spin * scale;
+    ball.spin *= SPIN_DECAY.powf(scale);
+}
+
+// Paddles put spin on the ball by moving as they hit it.
+pub fn spin_from_paddle(ball: &mut Ball, transform: &Transform, paddle_transform: &Transform) {
+    let facing = (transform.position.0 - paddle_transform.position.0).signum();
+    add_spin(ball, -paddle_transform.velocity.1 * facing * PADDLE_SPIN);
+}
+
+// Bullets clipping the edge of the ball set it spinning too.
+pub fn spin_from_bullet(ball: &mut Ball, transform: &Transform, bullet_transform: &Transform) {
+    let offset = (
+        bullet_transform.position.0 - transform.position.0,
+        bullet_transform.position.1 - transform.position.1,
+    );
+    let torque = offset.0 * bullet_transform.velocity.1 - offset.1 * bullet_transform.velocity.0;
+    add_spin(ball, torque / ball.radius.max(1.0) * BULLET_SPIN);
+}
+
+// Spin grips the wall on a bounce, kicking the ball along it and bleeding off half the spin.
+pub fn spin_off_wall(ball: &mut Ball, transform: &mut Transform, top: bool) {
+    let grip = if top { 1.0 } else { -1.0 };
+    transform.velocity.0 += ball.spin * grip * WALL_GRIP * ball.speed;
+    renormalise(transform, ball.speed);
+    ball.spin *= -0.5;
+}
+
+// A couple of spokes so players can actually see the spin.
+pub fn draw_spin(transform: &Transform, ball: &Ball, theme: &Theme) {
+    let strength = (ball.spin.abs() / MAX_SPIN).clamp(0.25, 1.0);
+    for spoke in 0..2 {
+        let angle = ball.angle + spoke as f32 * std::f32::consts::FRAC_PI_2;
+        let (sin, cos) = angle.sin_cos();
+        let reach = ball.radius - 4.0;
+        draw_line(
+            transform.position.0 - cos * reach,
+            transform.position.1 - sin * reach,
+            transform.position.0 + cos * reach,
+            transform.position.1 + sin * reach,
+            2.0,
+            Color {
+                a: strength,
+                ..theme.foreground
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square_distance;
+
+    // Moving and curving a spinning ball for so many steps, each `scale` of a normal one.
+    fn fly(steps: usize, scale: f32) -> (f32, f32) {
+        let mut transform = Transform {
+            position: (0.0, 0.0),
+            velocity: (6.0, 0.0),
+        };
+        let mut ball = Ball {
+            radius: 16.0,
+            speed: 6.0,
+            spin: MAX_SPIN,
+            ..Default::default()
+        };
+        for _ in 0..steps {
+            transform.position.0 += transform.velocity.0 * scale;
+            transform.position.1 += transform.velocity.1 * scale;
+            curve(&mut transform, &mut ball, scale);
+        }
+        transform.position
+    }
+
+    #[test]
+    fn slow_mo_follows_the_same_curve() {
+        let full = fly(120, 1.0);
+        // It's properly bent, not just off in a straight line.
+        assert!(full.1 > 100.0, "{:?}", full);
+        for (steps, scale) in [(240, 0.5), (480, 0.25)] {
+            let slow = fly(steps, scale);
+            let drift = square_distance(full.0, full.1, slow.0, slow.1).sqrt();
+            assert!(
+                drift < 2.0,
+                "{} off at x{}: {:?} vs {:?}",
+                drift,
+                scale,
+                full,
+                slow
+            );
+        }
+    }
+
+    #[test]
+    fn curving_keeps_the_speed() {
+        let mut transform = Transform {
+            position: (0.0, 0.0),
+            velocity: (3.0, 4.0),
+        };
+        let mut ball = Ball {
+            speed: 5.0,
+            spin: -MAX_SPIN,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            curve(&mut transform, &mut ball, 0.5);
+        }
+        let speed = (transform.velocity.0.powi(2) + transform.velocity.1.powi(2)).sqrt();
+        assert!((speed - 5.0).abs() < 1e-3);
+        assert!(ball.spin < 0.0 && ball.spin > -MAX_SPIN);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+// Where the sprite atlas is described. Without it (or without a particular sprite in it) things are
+// drawn with plain shapes like always.
+//
+// It looks like this, with the texture path relative to the atlas file:
+//
+//     texture atlas.png
+//     [paddle]
+//     frame 0 0 16 64   # x y width height, in texture pixels
+//     frame 16 0 16 64
+//     fps 8
+//     play loop         # or once
+pub const SPRITE_ATLAS_FILE: &str = "sprites/atlas.txt";
+
+// The sprites the game asks for.
+pub const PADDLE_SPRITE: &str = "paddle";
+pub const BALL_SPRITE: &str = "ball";
+pub const BULLET_SPRITE: &str = "bullet";
+pub const POWER_UP_SPRITE: &str = "power_up";
+
+// A component for anything that can be skinned. The tint gets multiplied over the texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub name: &'static str,
+    pub tint: Color,
+    pub started_at: f64, // When the animation started playing.
+    pub flip_x: bool,
+}
+
+impl Sprite {
+    pub fn new(name: &'static str) -> Self {
+        Sprite {
+            name,
+            tint: WHITE,
+            started_at: 0.0,
+            flip_x: false,
+        }
+    }
+
+    pub fn tint(self, tint: Color) -> Self {
+        Sprite { tint, ..self }
+    }
+
+    pub fn started_at(self, started_at: f64) -> Self {
+        Sprite { started_at, ..self }
+    }
+
+    pub fn flipped(self) -> Self {
+        Sprite {
+            flip_x: true,
+            ..self
+        }
+    }
+}
+
+// A run of frames out of the atlas texture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub frames: Vec<Rect>,
+    pub fps: f32,
+    pub looping: bool,
+}
+
+impl Animation {
+    fn new() -> Self {
+        Animation {
+            frames: Vec::new(),
+            fps: 0.0,
+            looping: true,
+        }
+    }
+
+    // The frame to show `elapsed` seconds in.
+    pub fn frame(&self, elapsed: f64) -> Option<Rect> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let index = (elapsed.max(0.0) * self.fps as f64) as usize;
+        let index = if self.looping {
+            index % self.frames.len()
+        } else {
+            index.min(self.frames.len() - 1)
+        };
+        Some(self.frames[index])
+    }
+}
+
+// One texture, cut up into named animations.
+pub struct SpriteAtlas {
+    texture: Option<Texture2D>,
+    animations: HashMap<String, Animation>,
+}
+
+impl SpriteAtlas {
+    // No texture, so nothing but shapes.
+    pub fn empty() -> Self {
+        SpriteAtlas {
+            texture: None,
+            animations: HashMap::new(),
+        }
+    }
+
+    // Loading the atlas and its texture, quietly falling back on shapes if there isn't one.
+    pub async fn load(path: &str) -> Self {
+        if !Path::new(path).exists() {
+            return SpriteAtlas::empty();
+        }
+        let loaded = match std::fs::read_to_string(path) {
+            Ok(source) => parse_atlas(&source),
+            Err(error) => Err(error.to_string()),
+        };
+        let (texture_path, animations) = match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                eprintln!("Couldn't load sprites, using shapes. ({}: {})", path, error);
+                return SpriteAtlas::empty();
+            }
+        };
+        let texture_path = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(texture_path);
+        match load_texture(&texture_path.to_string_lossy()).await {
+            Ok(texture) => {
+                texture.set_filter(FilterMode::Nearest);
+                SpriteAtlas {
+                    texture: Some(texture),
+                    animations,
+                }
+            }
+            Err(error) => {
+                eprintln!(
+                    "Couldn't load the sprite texture, using shapes. ({})",
+                    error
+                );
+                SpriteAtlas::empty()
+            }
+        }
+    }
+
+    // Drawing a sprite centred on `position` and stretched to `size`, turned by `rotation` radians.
+    // Returns false if there's nothing to draw it with, so the caller can fall back on shapes.
+    pub fn draw(
+        &self,
+        sprite: &Sprite,
+        position: (f32, f32),
+        size: (f32, f32),
+        rotation: f32,
+        current_time: f64,
+    ) -> bool {
+        let Some(texture) = &self.texture else {
+            return false;
+        };
+        let Some(frame) = self
+            .animations
+            .get(sprite.name)
+            .and_then(|animation| animation.frame(current_time - sprite.started_at))
+        else {
+            return false;
+        };
+        draw_texture_ex(
+            texture,
+            position.0 - size.0 / 2.0,
+            position.1 - size.1 / 2.0,
+            sprite.tint,
+            DrawTextureParams {
+                dest_size: Some(vec2(size.0, size.1)),
+                source: Some(frame),
+                rotation,
+                flip_x: sprite.flip_x,
+                ..Default::default()
+            },
+        );
+        true
+    }
+}
+
+// Reading an atlas description into the texture's path and its animations.
+pub fn parse_atlas(source: &str) -> Result<(String, HashMap<String, Animation>), String> {
+    let mut texture = None;
+    let mut animations: HashMap<String, Animation> = HashMap::new();
+    let mut current: Option<String> = None;
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(name) = text
+            .strip_prefix('[')
+            .and_then(|text| text.strip_suffix(']'))
+        {
+            animations.insert(name.trim().to_string(), Animation::new());
+            current = Some(name.trim().to_string());
+            continue;
+        }
+
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let (keyword, rest) = (words[0], &words[1..]);
+        if keyword == "texture" {
+            texture = Some(rest.join(" "));
+            continue;
+        }
+        let Some(animation) = current.as_ref().and_then(|name| animations.get_mut(name)) else {
+            return Err(format!("line {}: setting outside of a [sprite]", line));
+        };
+        if keyword == "play" {
+            animation.looping = match rest.first().copied() {
+                Some("loop") => true,
+                Some("once") => false,
+                _ => return Err(format!("line {}: play takes loop or once", line)),
+            };
+            continue;
+        }
+        let n = rest
+            .iter()
+            .map(|word| {
+                word.parse::<f32>()
+                    .map_err(|_| format!("line {}: '{}' isn't a number", line, word))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let wanted = match keyword {
+            "fps" => 1,
+            "frame" => 4,
+            other => return Err(format!("line {}: unknown setting '{}'", line, other)),
+        };
+        if n.len() != wanted {
+            return Err(format!(
+                "line {}: {} takes {} numbers, found {}",
+                line,
+                keyword,
+                wanted,
+                n.len()
+            ));
+        }
+        match keyword {
+            "fps" => animation.fps = n[0],
+            _ => animation.frames.push(Rect::new(n[0], n[1], n[2], n[3])),
+        }
+    }
+    let texture = texture.ok_or("no texture given")?;
+    Ok((texture, animations))
+}
@@ -0,0 +1,316 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hecs::*;
+use macroquad::prelude::*;
+
+use crate::arena::arena_width;
+use crate::hud::{Anchor, Hud};
+use crate::theme::Theme;
+use crate::{Bounds, Transform};
+
+// Where finished matches get written out to.
+pub const STATS_DIRECTORY: &str = "stats";
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    // Whichever half of the arena `x` is in.
+    pub fn of(x: f32) -> Side {
+        if x < arena_width() / 2.0 {
+            Side::Left
+        } else {
+            Side::Right
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+// What one side got up to.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct SideStats {
+    pub shots_fired: u32,
+    pub shots_hit_ball: u32,   // Bullets that knocked a ball about.
+    pub shots_hit_paddle: u32, // And ones that chipped a paddle.
+    pub damage_taken: f32,     // In paddle length.
+    pub returns: u32,          // Balls sent back off the paddle.
+    pub possession: f32,       // Seconds as the last side to touch a ball.
+}
+
+impl SideStats {
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            (self.shots_hit_ball + self.shots_hit_paddle) as f32 / self.shots_fired as f32
+        }
+    }
+}
+
+// Everything worth knowing about a match, from the serve to the win.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct MatchStats {
+    pub sides: [SideStats; 2],
+    pub longest_rally: u32, // Paddle hits in a row before a goal.
+    pub peak_speed: f32,
+    pub duration: f32,
+    pub winner: Option<Side>,
+    pub knockout: bool,
+    pub mode: String,
+    pub level: String,
+    pub finished_at: u64, // Seconds since the Unix epoch.
+}
+
+impl MatchStats {
+    pub fn new(mode: &str, level: &str) -> Self {
+        MatchStats {
+            mode: mode.to_string(),
+            level: level.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn side(&self, side: Side) -> &SideStats {
+        &self.sides[side as usize]
+    }
+
+    pub fn side_mut(&mut self, side: Side) -> &mut SideStats {
+        &mut self.sides[side as usize]
+    }
+
+    pub fn finished(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    // Keeping track of the fastest ball and the longest rally as they go.
+    pub fn track_ball(&mut self, speed: f32, rally: u32) {
+        self.peak_speed = self.peak_speed.max(speed);
+        self.longest_rally = self.longest_rally.max(rally);
+    }
+
+    pub fn finish(&mut self, winner: Side, knockout: bool) {
+        self.winner = Some(winner);
+        self.knockout = knockout;
+        self.finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+    }
+
+    // A stat by name, for the achievements to check against.
+    pub fn stat(&self, side: Side, name: &str) -> Option<f32> {
+        let stats = self.side(side);
+        Some(match name {
+            "shots_fired" => stats.shots_fired as f32,
+            "shots_hit" => (stats.shots_hit_ball + stats.shots_hit_paddle) as f32,
+            "shots_hit_ball" => stats.shots_hit_ball as f32,
+            "shots_hit_paddle" => stats.shots_hit_paddle as f32,
+            "damage_taken" => stats.damage_taken,
+            "returns" => stats.returns as f32,
+            "possession" => stats.possession,
+            "longest_rally" => self.longest_rally as f32,
+            "peak_speed" => self.peak_speed,
+            "duration" => self.duration,
+            "knockout" => self.knockout as i32 as f32,
+            _ => return None,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let side = |stats: &SideStats| {
+            format!(
+                "{{\"shots_fired\": {}, \"shots_hit_ball\": {}, \"shots_hit_paddle\": {}, \"accuracy\": {}, \"damage_taken\": {}, \"returns\": {}, \"possession\": {}}}",
+                stats.shots_fired,
+                stats.shots_hit_ball,
+                stats.shots_hit_paddle,
+                stats.accuracy(),
+                stats.damage_taken,
+                stats.returns,
+                stats.possession
+            )
+        };
+        format!(
+            "{{\n  \"finished_at\": {},\n  \"mode\": {},\n  \"level\": {},\n  \"winner\": {},\n  \"knockout\": {},\n  \"duration\": {},\n  \"longest_rally\": {},\n  \"peak_speed\": {},\n  \"left\": {},\n  \"right\": {}\n}}\n",
+            self.finished_at,
+            json_string(&self.mode),
+            json_string(&self.level),
+            self.winner
+                .map_or("null".to_string(), |winner| json_string(winner.label())),
+            self.knockout,
+            self.duration,
+            self.longest_rally,
+            self.peak_speed,
+            side(self.side(Side::Left)),
+            side(self.side(Side::Right))
+        )
+    }
+
+    // Writing the match out for looking over later, returning where it went.
+    pub fn export(&self, directory: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| format!("{}: {}", directory.display(), error))?;
+        let path = directory.join(format!("match-{}.json", self.finished_at));
+        std::fs::write(&path, self.to_json())
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(path)
+    }
+
+    // The post-match rundown, in the middle of the screen.
+    pub fn draw(&self, hud: &Hud, theme: &Theme) {
+        let rows = [
+            (
+                "Shots fired",
+                format!("{}", self.side(Side::Left).shots_fired),
+                format!("{}", self.side(Side::Right).shots_fired),
+            ),
+            (
+                "Hit ball / paddle",
+                format!(
+                    "{} / {}",
+                    self.side(Side::Left).shots_hit_ball,
+                    self.side(Side::Left).shots_hit_paddle
+                ),
+                format!(
+                    "{} / {}",
+                    self.side(Side::Right).shots_hit_ball,
+                    self.side(Side::Right).shots_hit_paddle
+                ),
+            ),
+            (
+                "Damage taken",
+                format!("{}", self.side(Side::Left).damage_taken.round()),
+                format!("{}", self.side(Side::Right).damage_taken.round()),
+            ),
+            (
+                "Returns",
+                format!("{}", self.side(Side::Left).returns),
+                format!("{}", self.side(Side::Right).returns),
+            ),
+            (
+                "Possession",
+                format!("{:.1}s", self.side(Side::Left).possession),
+                format!("{:.1}s", self.side(Side::Right).possession),
+            ),
+        ];
+        let top = -60.0 - rows.len() as f32 * 12.0;
+        for (i, (label, left, right)) in rows.iter().enumerate() {
+            let y = top + i as f32 * 24.0;
+            hud.text(label, Anchor::Center, (0.0, y), 20.0, theme.muted);
+            hud.text(left, Anchor::Center, (-180.0, y), 20.0, theme.left);
+            hud.text(right, Anchor::Center, (180.0, y), 20.0, theme.right);
+        }
+        let summary = format!(
+            "Longest rally {}  Peak speed {:.1}  Lasted {:.0}s",
+            self.longest_rally, self.peak_speed, self.duration
+        );
+        hud.text(
+            &summary,
+            Anchor::Center,
+            (0.0, top + rows.len() as f32 * 24.0 + 8.0),
+            20.0,
+            theme.foreground,
+        );
+    }
+}
+
+// Which side each paddle's on, for working out who did what.
+pub fn paddle_sides(world: &World) -> Vec<(Entity, Side)> {
+    world
+        .query::<(&Transform, &Bounds)>()
+        .iter()
+        .map(|(id, (transform, _bounds))| (id, Side::of(transform.position.0)))
+        .collect()
+}
+
+pub fn side_of(sides: &[(Entity, Side)], entity: Option<Entity>) -> Option<Side> {
+    let entity = entity?;
+    sides
+        .iter()
+        .find(|(id, _side)| *id == entity)
+        .map(|(_id, side)| *side)
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            c if (c as u32) < 0x20 => escaped += &format!("\\u{:04x}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_string_quotes_plain_text() {
+        assert_eq!(json_string(""), "\"\"");
+        assert_eq!(json_string("Classic"), "\"Classic\"");
+        assert_eq!(json_string("Café ⚽"), "\"Café ⚽\"");
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_and_backslashes() {
+        assert_eq!(json_string("say \"hi\""), r#""say \"hi\"""#);
+        assert_eq!(json_string(r"C:\levels\new"), r#""C:\\levels\\new""#);
+        assert_eq!(json_string(r#"\""#), r#""\\\"""#);
+    }
+
+    #[test]
+    fn json_string_escapes_control_characters() {
+        assert_eq!(json_string("a\nb"), r#""a\nb""#);
+        assert_eq!(json_string("a\tb"), r#""a\u0009b""#);
+        assert_eq!(json_string("\r\u{0}\u{1f}"), r#""\u000d\u0000\u001f""#);
+        // Just past the control characters is left alone.
+        assert_eq!(json_string(" "), "\" \"");
+    }
+
+    #[test]
+    fn accuracy_without_any_shots_is_zero() {
+        let stats = SideStats::default();
+        assert_eq!(stats.accuracy(), 0.0);
+        let stats = SideStats {
+            shots_fired: 8,
+            shots_hit_ball: 1,
+            shots_hit_paddle: 3,
+            ..Default::default()
+        };
+        assert_eq!(stats.accuracy(), 0.5);
+    }
+
+    #[test]
+    fn to_json_escapes_names_and_never_writes_nan() {
+        let stats = MatchStats::new("Multiball", "The \"Pit\"\n");
+        let json = stats.to_json();
+        assert!(json.contains(r#""level": "The \"Pit\"\n","#), "{}", json);
+        assert!(json.contains(r#""mode": "Multiball","#));
+        assert!(json.contains(r#""winner": null,"#));
+        assert!(json.contains(r#""accuracy": 0,"#));
+        assert!(!json.contains("NaN"));
+    }
+
+    #[test]
+    fn to_json_names_the_winner() {
+        let mut stats = MatchStats::new("Classic", "Classic");
+        stats.finish(Side::Right, true);
+        let json = stats.to_json();
+        assert!(json.contains(r#""winner": "right","#), "{}", json);
+        assert!(json.contains(r#""knockout": true,"#));
+    }
+}
@@ -0,0 +1,381 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use macroquad::prelude::*;
+
+use crate::hud::{Anchor, Hud};
+use crate::theme::Theme;
+
+// Where the best survival runs are kept, and how many of them.
+pub const LEADERBOARD_FILE: &str = "leaderboard.txt";
+pub const LEADERBOARD_SIZE: usize = 10;
+const INITIALS_LENGTH: usize = 3;
+
+// How long it takes the AI to step up a level, in seconds.
+const LEVEL_TIME: f64 = 20.0;
+
+// How far into a survival run things are, starting from level 1.
+pub fn survival_level(elapsed: f64) -> u32 {
+    1 + (elapsed.max(0.0) / LEVEL_TIME) as u32
+}
+
+// Seconds between the AI's shots. It doesn't shoot at all on the first level.
+pub fn ai_fire_cooldown(level: u32) -> Option<f64> {
+    if level < 2 {
+        None
+    } else {
+        Some((2.4 - level as f64 * 0.2).max(0.4))
+    }
+}
+
+// How hard the AI can steer, on top of the usual.
+pub fn ai_tracking(level: u32) -> f32 {
+    1.0 + (level - 1) as f32 * 0.15
+}
+
+// How much faster balls get served.
+pub fn serve_speed(level: u32) -> f32 {
+    1.0 + (level - 1) as f32 * 0.1
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// A Unix timestamp as a plain year-month-day date.
+pub fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days-to-civil, shifted so years start in March.
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// One run worth remembering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    pub initials: String,
+    pub returns: u32,  // The score, really.
+    pub survived: f32, // Seconds, for breaking ties.
+    pub date: String,
+    pub seed: u64,
+    pub settings: String, // The level and rules it was played with.
+}
+
+impl LeaderboardEntry {
+    // Whether this run beats another.
+    fn beats(&self, other: &LeaderboardEntry) -> bool {
+        (self.returns, self.survived) > (other.returns, other.survived)
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.initials, self.returns, self.survived, self.date, self.seed, self.settings
+        )
+    }
+
+    fn parse(line: &str) -> Option<LeaderboardEntry> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let [initials, returns, survived, date, seed, settings] = fields[..] else {
+            return None;
+        };
+        Some(LeaderboardEntry {
+            initials: initials.to_string(),
+            returns: returns.parse().ok()?,
+            survived: survived.parse().ok()?,
+            date: date.to_string(),
+            seed: seed.parse().ok()?,
+            settings: settings.to_string(),
+        })
+    }
+}
+
+// The best runs, best first.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    // Loading the leaderboard, skipping over any lines that don't make sense.
+    pub fn load(path: &Path) -> Leaderboard {
+        let Ok(source) = std::fs::read_to_string(path) else {
+            return Leaderboard::default();
+        };
+        let mut entries = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match LeaderboardEntry::parse(line) {
+                Some(entry) => entries.push(entry),
+                None => eprintln!(
+                    "Skipping a broken leaderboard entry. ({}: line {})",
+                    path.display(),
+                    index + 1
+                ),
+            }
+        }
+        let mut leaderboard = Leaderboard { entries };
+        leaderboard.tidy();
+        leaderboard
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = self
+            .entries
+            .iter()
+            .map(|entry| entry.to_line() + "\n")
+            .collect::<String>();
+        std::fs::write(path, text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    fn tidy(&mut self) {
+        self.entries.sort_by(|a, b| {
+            (b.returns, b.survived)
+                .partial_cmp(&(a.returns, a.survived))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        self.entries.truncate(LEADERBOARD_SIZE);
+    }
+
+    // Whether a run would make it onto the board.
+    pub fn qualifies(&self, entry: &LeaderboardEntry) -> bool {
+        entry.returns > 0
+            && (self.entries.len() < LEADERBOARD_SIZE
+                || self.entries.iter().any(|other| entry.beats(other)))
+    }
+
+    pub fn insert(&mut self, entry: LeaderboardEntry) {
+        self.entries.push(entry);
+        self.tidy();
+    }
+
+    pub fn draw(&self, hud: &Hud, theme: &Theme, highlight: Option<&LeaderboardEntry>) {
+        let top = -40.0 - LEADERBOARD_SIZE as f32 * 10.0;
+        hud.text(
+            "Survival leaderboard",
+            Anchor::Center,
+            (0.0, top - 32.0),
+            24.0,
+            theme.foreground,
+        );
+        if self.entries.is_empty() {
+            hud.text(
+                "Nobody's survived yet.",
+                Anchor::Center,
+                (0.0, top),
+                20.0,
+                theme.muted,
+            );
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
+            let color = if highlight == Some(entry) {
+                theme.warning
+            } else {
+                theme.muted
+            };
+            let line = format!(
+                "{:>2}. {:<3} {:>4} returns {:>6.1}s  {}  seed {}  {}",
+                i + 1,
+                entry.initials,
+                entry.returns,
+                entry.survived,
+                entry.date,
+                entry.seed,
+                entry.settings
+            );
+            hud.text(
+                &line,
+                Anchor::Center,
+                (0.0, top + i as f32 * 22.0),
+                18.0,
+                color,
+            );
+        }
+    }
+}
+
+// Typing in initials for a run that made the board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitialsEntry {
+    pub initials: String,
+    pub entry: LeaderboardEntry,
+}
+
+impl InitialsEntry {
+    pub fn new(entry: LeaderboardEntry) -> Self {
+        // Clearing out anything typed before now.
+        while get_char_pressed().is_some() {}
+        InitialsEntry {
+            initials: String::new(),
+            entry,
+        }
+    }
+
+    // Taking this frame's typing. Returns the finished entry once it's been confirmed.
+    pub fn update(&mut self) -> Option<LeaderboardEntry> {
+        while let Some(c) = get_char_pressed() {
+            if c.is_ascii_alphanumeric() && self.initials.len() < INITIALS_LENGTH {
+                self.initials.push(c.to_ascii_uppercase());
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.initials.pop();
+        }
+        if is_key_pressed(KeyCode::Enter) && !self.initials.is_empty() {
+            return Some(LeaderboardEntry {
+                initials: self.initials.clone(),
+                ..self.entry.clone()
+            });
+        }
+        None
+    }
+
+    pub fn draw(&self, hud: &Hud, theme: &Theme, current_time: f64) {
+        let cursor = if (current_time * 2.0) % 2.0 < 1.0 {
+            "_"
+        } else {
+            " "
+        };
+        let shown = format!(
+            "{}{}",
+            self.initials,
+            if self.initials.len() < INITIALS_LENGTH {
+                cursor
+            } else {
+                ""
+            }
+        );
+        hud.text(
+            &format!(
+                "New high score! {} returns. Enter your initials: {}",
+                self.entry.returns, shown
+            ),
+            Anchor::Center,
+            (0.0, -40.0),
+            24.0,
+            theme.warning,
+        );
+        hud.text(
+            "[Enter] to save",
+            Anchor::Center,
+            (0.0, -10.0),
+            20.0,
+            theme.muted,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &str, returns: u32, survived: f32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            initials: initials.to_string(),
+            returns,
+            survived,
+            date: "2024-02-29".to_string(),
+            seed: 1234567890123,
+            settings: "Classic, KO off".to_string(),
+        }
+    }
+
+    #[test]
+    fn format_date_known_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(86399), "1970-01-01"); // The last second of the day.
+        assert_eq!(format_date(86400), "1970-01-02");
+        assert_eq!(format_date(946598400), "1999-12-31");
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(951868800), "2000-03-01");
+        assert_eq!(format_date(1709164800), "2024-02-29");
+        assert_eq!(format_date(4107542400), "2100-03-01"); // 2100 isn't a leap year.
+    }
+
+    #[test]
+    fn entries_round_trip_through_lines() {
+        let original = entry("ABC", 42, 61.5);
+        let line = original.to_line();
+        assert_eq!(line.split('\t').count(), 6);
+        assert_eq!(LeaderboardEntry::parse(&line), Some(original));
+    }
+
+    #[test]
+    fn broken_lines_dont_parse() {
+        assert_eq!(LeaderboardEntry::parse(""), None);
+        assert_eq!(LeaderboardEntry::parse("ABC\t42\t61.5"), None);
+        assert_eq!(
+            LeaderboardEntry::parse("ABC\tlots\t61.5\t2024-02-29\t1\tClassic"),
+            None
+        );
+        assert_eq!(
+            LeaderboardEntry::parse("ABC\t42\t61.5\t2024-02-29\t1\tClassic\textra"),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_best_runs_in_order() {
+        let mut leaderboard = Leaderboard::default();
+        for i in 0..LEADERBOARD_SIZE as u32 + 5 {
+            // Shuffled about a bit, so they don't go in already sorted.
+            leaderboard.insert(entry("AAA", (i * 7) % 15, i as f32));
+        }
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+        let returns = leaderboard
+            .entries
+            .iter()
+            .map(|entry| entry.returns)
+            .collect::<Vec<_>>();
+        assert_eq!(returns, (5..15).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ties_go_to_whoever_survived_longer() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(entry("SHO", 10, 30.0));
+        leaderboard.insert(entry("LNG", 10, 90.0));
+        assert_eq!(leaderboard.entries[0].initials, "LNG");
+        assert_eq!(leaderboard.entries[1].initials, "SHO");
+    }
+
+    #[test]
+    fn qualifying_for_a_full_board() {
+        let mut leaderboard = Leaderboard::default();
+        assert!(!leaderboard.qualifies(&entry("ZZZ", 0, 100.0))); // No returns, no entry.
+        assert!(leaderboard.qualifies(&entry("ZZZ", 1, 0.0)));
+        for i in 0..LEADERBOARD_SIZE as u32 {
+            leaderboard.insert(entry("AAA", 10 + i, 10.0));
+        }
+        assert!(!leaderboard.qualifies(&entry("ZZZ", 10, 10.0))); // Only ties the worst.
+        assert!(leaderboard.qualifies(&entry("ZZZ", 10, 10.5)));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = std::env::temp_dir().join(format!("pwg-leaderboard-{}.txt", std::process::id()));
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(entry("ONE", 5, 12.25));
+        leaderboard.insert(entry("TWO", 8, 40.0));
+        leaderboard.save(&path).unwrap();
+        let loaded = Leaderboard::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, leaderboard);
+    }
+}
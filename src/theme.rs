@@ -0,0 +1,122 @@
+use macroquad::prelude::*;
+
+// How much of the usual motion is left with reduced motion switched on.
+pub const REDUCED_MOTION: f32 = 0.2;
+
+// Every colour the arena is drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub glow: Color,  // What the background drifts towards as the intensity builds.
+    pub left: Color,  // The left side's colour, for its score tint and goal bursts.
+    pub right: Color, // And the right side's.
+    pub score_tint: f32, // How much each side's score tints the background.
+    pub foreground: Color,
+    pub shadow: Color, // Fills and trails.
+    pub muted: Color,  // Menu text.
+    pub dim: Color,    // Anything knocked out.
+    pub warning: Color,
+    pub danger: Color,
+    pub hazard: Color, // Blocks that still have a few hits left in them.
+    pub portal: Color,
+    pub parry: Color,
+}
+
+pub const THEMES: [Theme; 4] = [
+    Theme {
+        name: "Classic",
+        background: BLACK,
+        glow: WHITE,
+        left: RED,
+        right: BLUE,
+        score_tint: 1.0,
+        foreground: WHITE,
+        shadow: BLACK,
+        muted: GRAY,
+        dim: DARKGRAY,
+        warning: YELLOW,
+        danger: RED,
+        hazard: ORANGE,
+        portal: VIOLET,
+        parry: SKYBLUE,
+    },
+    // Okabe-Ito colours, which hold up for red-green colour blindness.
+    Theme {
+        name: "Colour-blind safe",
+        background: BLACK,
+        glow: WHITE,
+        left: Color::new(0.90, 0.62, 0.0, 1.0),
+        right: Color::new(0.0, 0.45, 0.70, 1.0),
+        score_tint: 1.0,
+        foreground: WHITE,
+        shadow: BLACK,
+        muted: GRAY,
+        dim: DARKGRAY,
+        warning: Color::new(0.94, 0.89, 0.26, 1.0),
+        danger: Color::new(0.84, 0.37, 0.0, 1.0),
+        hazard: Color::new(0.90, 0.62, 0.0, 1.0),
+        portal: Color::new(0.80, 0.47, 0.65, 1.0),
+        parry: Color::new(0.34, 0.71, 0.91, 1.0),
+    },
+    // Pink against teal, for blue-yellow colour blindness.
+    Theme {
+        name: "Tritan safe",
+        background: BLACK,
+        glow: WHITE,
+        left: Color::new(0.86, 0.15, 0.50, 1.0),
+        right: Color::new(0.0, 0.62, 0.58, 1.0),
+        score_tint: 1.0,
+        foreground: WHITE,
+        shadow: BLACK,
+        muted: GRAY,
+        dim: DARKGRAY,
+        warning: Color::new(1.0, 0.55, 0.70, 1.0),
+        danger: Color::new(0.86, 0.15, 0.50, 1.0),
+        hazard: Color::new(1.0, 0.55, 0.70, 1.0),
+        portal: Color::new(0.55, 0.85, 0.80, 1.0),
+        parry: Color::new(0.0, 0.62, 0.58, 1.0),
+    },
+    // A flat black arena with nothing washing over it, and bright, fully saturated everything else.
+    Theme {
+        name: "High contrast",
+        background: BLACK,
+        glow: BLACK,
+        left: YELLOW,
+        right: Color::new(0.0, 1.0, 1.0, 1.0),
+        score_tint: 0.0,
+        foreground: WHITE,
+        shadow: BLACK,
+        muted: LIGHTGRAY,
+        dim: GRAY,
+        warning: YELLOW,
+        danger: Color::new(1.0, 0.2, 0.2, 1.0),
+        hazard: YELLOW,
+        portal: Color::new(1.0, 0.0, 1.0, 1.0),
+        parry: Color::new(0.0, 1.0, 1.0, 1.0),
+    },
+];
+
+impl Theme {
+    // The colour the background is heading towards, given how intense things are and the score.
+    pub fn background_for(
+        &self,
+        intensity: f32,
+        flash: f32,
+        left_score: i32,
+        right_score: i32,
+    ) -> Color {
+        let glow = intensity / 400.0;
+        let left = self.score_tint * left_score as f32 / 50.0;
+        let right = self.score_tint * right_score as f32 / 50.0;
+        let channel = |base: f32, glow_channel: f32, left_channel: f32, right_channel: f32| {
+            base + glow * glow_channel + flash + left * left_channel + right * right_channel
+        };
+        Color::new(
+            channel(self.background.r, self.glow.r, self.left.r, self.right.r),
+            channel(self.background.g, self.glow.g, self.left.g, self.right.g),
+            channel(self.background.b, self.glow.b, self.left.b, self.right.b),
+            1.0,
+        )
+    }
+}